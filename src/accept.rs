@@ -0,0 +1,116 @@
+use std::io::{self, BufRead, Write};
+
+use anyhow::{Context, Result};
+use console::style;
+use glob::Pattern;
+
+use crate::{
+    client::{Client, RequestsConfig, Response},
+    diff::{self, Renderer},
+};
+
+/// Accepts the right side of every different response whose request name matches the glob
+/// pattern, `*` accepting all of them. Requests whose left side isn't cached are skipped with a
+/// warning. Returns the number of accepted responses.
+pub fn accept_matching(
+    client: &Client,
+    requests: &[RequestsConfig],
+    responses: &[Response],
+    pattern: &str,
+) -> Result<usize> {
+    let pattern =
+        Pattern::new(pattern).with_context(|| format!("Invalid accept pattern {pattern}"))?;
+
+    let mut accepted = 0;
+    for (request, response) in different(requests, responses) {
+        if pattern.matches(&request.name) && is_cached(request) {
+            accept(client, request, response)?;
+            accepted += 1;
+        }
+    }
+
+    Ok(accepted)
+}
+
+/// Shows the differences of every different response and asks whether to accept its right side.
+/// Requests whose left side isn't cached are skipped with a warning. Returns the number of
+/// accepted responses.
+pub fn accept_interactive(
+    client: &Client,
+    requests: &[RequestsConfig],
+    responses: &[Response],
+    renderer: Renderer,
+) -> Result<usize> {
+    let (terminal_width, _) = term_size::dimensions().unwrap_or((100, 100));
+    let mut accept_all = false;
+    let mut accepted = 0;
+    for (request, response) in different(requests, responses) {
+        if !is_cached(request) {
+            continue;
+        }
+
+        if !accept_all {
+            println!(
+                "{}",
+                diff::format_response(response, renderer, terminal_width)?
+            );
+
+            match prompt(&request.name)?.as_str() {
+                "y" | "yes" => {}
+                "a" | "all" => accept_all = true,
+                "q" | "quit" => break,
+                _ => continue,
+            }
+        }
+
+        accept(client, request, response)?;
+        accepted += 1;
+    }
+
+    Ok(accepted)
+}
+
+fn different<'a>(
+    requests: &'a [RequestsConfig],
+    responses: &'a [Response],
+) -> impl Iterator<Item = (&'a RequestsConfig, &'a Response)> {
+    responses
+        .iter()
+        .filter(|response| !response.is_equal())
+        .filter_map(|response| {
+            requests
+                .iter()
+                .find(|request| request.name == response.name)
+                .map(|request| (request, response))
+        })
+}
+
+fn is_cached(request: &RequestsConfig) -> bool {
+    let cached = request.left.is_cached();
+    if !cached {
+        println!(
+            "{} {} can't be accepted, its left side is not cached",
+            style("skipped:").yellow(),
+            request.name
+        );
+    }
+    cached
+}
+
+fn accept(client: &Client, request: &RequestsConfig, response: &Response) -> Result<()> {
+    client.accept(request, response)?;
+    println!("{} {}", style("accepted:").green(), request.name);
+    Ok(())
+}
+
+fn prompt(name: &str) -> Result<String> {
+    print!("Accept the right side of {name}? [y]es, [n]o, [a]ll remaining, [q]uit: ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("Failed to read answer")?;
+    Ok(answer.trim().to_lowercase())
+}
@@ -1,19 +1,31 @@
 #![allow(clippy::doc_markdown, clippy::struct_excessive_bools)]
 use anyhow::{Context, Error};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
+use glob::Pattern;
+use regex::Regex;
 use std::{collections::HashMap, path::PathBuf};
 
-use crate::client::Config;
+use crate::{
+    client::{Config, captures},
+    diff::Renderer,
+    load,
+    report::ReportFormat,
+};
 
 #[derive(Parser, Debug)]
 #[command(
     author,
     version,
     about,
-    long_about = "Takes multiple web links and compare their results between eachother"
+    long_about = "Takes multiple web links and compare their results between eachother",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Path of the toml file format to load for urls configurations. The configuration should be list of requests, each request has name, and a left and right list of options. Here is a snippet, check README.md for full list of options.
     /// {n}
     /// [[requests]]{n}
@@ -32,7 +44,28 @@ pub struct Args {
     /// Environmental variables can be used, either by providing them on the command level or by including them in a `.env` file. to use them inside the json wrap them in a ${}
     ///  Example: if we have an environmental variable `HOST=https://google.com` and we use `"url": "${HOST}/query` when the program runs it will resolve to `"url": "https://google.com/query`
     /// Environmental variables can be used by wrapping them in `${}` within any string value inside the TOML config.
-    pub path: PathBuf,
+    /// {n}
+    /// Several configs can be given, their requests run together as a single config.
+    #[arg(required = true, value_name = "PATHS")]
+    pub paths: Vec<PathBuf>,
+
+    /// Only run the requests whose name matches this glob pattern, can be given multiple times
+    #[arg(long = "only", value_name = "PATTERN")]
+    pub only: Vec<String>,
+
+    /// Only run the requests whose name matches this regular expression, can be given multiple
+    /// times and along with `--only`
+    #[arg(long = "only-regex", value_name = "REGEX")]
+    pub only_regex: Vec<String>,
+
+    /// Only run the requests with this tag, can be given multiple times to run the requests with
+    /// any of them
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tag: Vec<String>,
+
+    /// Leave out the requests with this tag, can be given multiple times
+    #[arg(long = "exclude-tag", value_name = "TAG")]
+    pub exclude_tag: Vec<String>,
 
     /// Take n requests from the config
     #[arg(short = 't', long = "take")]
@@ -54,10 +87,84 @@ pub struct Args {
     #[arg(short = 'n', long = "no-cache")]
     pub no_cache: bool,
 
-    /// Skip all the ignore lines
+    /// Skip all the ignore lines and ignore paths
     #[arg(short = 'i', long = "skip-ignore")]
     pub skip_ignore: bool,
 
+    /// Only compare status codes and ignore the bodies
+    #[arg(long = "status-only")]
+    pub status_only: bool,
+
+    /// CI mode, print differences without a pager followed by a summary. Exits with 0 when all
+    /// responses are equal, 1 when differences are found and 2 when any request failed
+    #[arg(long = "check")]
+    pub check: bool,
+
+    /// Generate a machine readable report, printed to stdout instead of the differences unless
+    /// `--report-path` is given
+    #[arg(long = "report", value_enum)]
+    pub report: Option<ReportFormat>,
+
+    /// Write the report to this path instead of stdout
+    #[arg(long = "report-path", requires = "report")]
+    pub report_path: Option<PathBuf>,
+
+    /// Record all the responses of this run into a named snapshot, replacing an existing one with
+    /// the same name
+    #[arg(long = "snapshot", value_name = "NAME", conflicts_with_all = ["against", "compare_snapshots"])]
+    pub snapshot: Option<String>,
+
+    /// List the recorded snapshots for this toml config
+    #[arg(long = "list-snapshots")]
+    pub list_snapshots: bool,
+
+    /// Serve the left side of every request from a recorded snapshot and compare it against the
+    /// live right side
+    #[arg(
+        long = "against",
+        value_name = "NAME",
+        conflicts_with = "compare_snapshots"
+    )]
+    pub against: Option<String>,
+
+    /// Compare two recorded snapshots against each other without making any calls, the left side
+    /// is served from the first and the right side from the second
+    #[arg(long = "compare-snapshots", num_args = 2, value_names = ["LEFT", "RIGHT"])]
+    pub compare_snapshots: Option<Vec<String>>,
+
+    /// Maximum number of requests running at once, each request sends its left and right side
+    #[arg(long = "concurrency", value_name = "N")]
+    pub concurrency: Option<usize>,
+
+    /// Send the right side of each request only after the left side finished
+    #[arg(long = "sequential")]
+    pub sequential: bool,
+
+    /// Run all requests n times and show the latency percentiles of each request, differences are
+    /// shown for the last run
+    #[arg(long = "repeat", value_name = "N", default_value_t = 1)]
+    pub repeat: usize,
+
+    /// Accept the right side of every different request whose name matches this glob pattern, `*`
+    /// for all, by storing it in the cache as the expected left side
+    #[arg(
+        long = "accept",
+        value_name = "PATTERN",
+        conflicts_with_all = ["accept_interactive", "snapshot", "against", "compare_snapshots", "check", "report", "out", "tui"]
+    )]
+    pub accept: Option<String>,
+
+    /// Show the differences of every different request and ask whether to accept its right side
+    #[arg(
+        long = "accept-interactive",
+        conflicts_with_all = ["snapshot", "against", "compare_snapshots", "check", "report", "out", "tui"]
+    )]
+    pub accept_interactive: bool,
+
+    /// Browse the results in an interactive terminal UI instead of printing the differences
+    #[arg(long = "tui", conflicts_with_all = ["check", "report", "out", "cache_only"])]
+    pub tui: bool,
+
     /// Only cache calls and don't show differences
     #[arg(long = "cache-only")]
     pub cache_only: bool,
@@ -65,6 +172,58 @@ pub struct Args {
     /// Output a file config for only calls that have differences
     #[arg(short = 'o', long = "out")]
     pub out: Option<PathBuf>,
+
+    /// How differences are shown, `delta` requires `git-delta` to be installed
+    #[arg(short = 'r', long = "renderer", value_enum, default_value_t = Renderer::SideBySide)]
+    pub renderer: Renderer,
+}
+
+impl Args {
+    /// Name of the configs used for their cache, snapshots and reports, the file names of all the
+    /// given paths joined by `+`.
+    pub fn config_name(&self) -> anyhow::Result<String> {
+        let names = self
+            .paths
+            .iter()
+            .map(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .with_context(|| format!("Failed to retreive file name of {}", path.display()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(names.join("+"))
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Generate a toml config from an OpenAPI 3 document, with a request for every GET operation
+    Generate(GenerateArgs),
+
+    /// Generate a toml config from a HAR export of a browser or from a file of curl commands
+    Import(ImportArgs),
+
+    /// Validate a toml config without sending any request, reporting every problem with its line
+    Check(CheckArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct GenerateArgs {
+    /// Path of the OpenAPI 3 document, either YAML or JSON
+    #[arg(long = "openapi")]
+    pub openapi: PathBuf,
+
+    /// Base URL of the left side, defaults to the first server of the document
+    #[arg(long = "left-base")]
+    pub left_base: Option<String>,
+
+    /// Base URL of the right side, defaults to the first server of the document
+    #[arg(long = "right-base")]
+    pub right_base: Option<String>,
+
+    /// Write the config to this path instead of stdout
+    #[arg(short = 'o', long = "out")]
+    pub out: Option<PathBuf>,
 }
 
 impl TryFrom<&Args> for Config {
@@ -74,47 +233,70 @@ impl TryFrom<&Args> for Config {
         dotenv().ok();
         let envs: HashMap<String, String> = std::env::vars().collect();
 
-        let toml = std::fs::read_to_string(&args.path)
-            .map(|toml| process_env_variables(&toml, &envs))
-            .map_err(|e| Error::msg(format!("Failed to read {}: {}", args.path.display(), e)))?;
+        let mut config = load::load(&args.paths, &envs)?;
+
+        let only = args
+            .only
+            .iter()
+            .map(|pattern| {
+                Pattern::new(pattern).with_context(|| format!("Invalid --only pattern {pattern}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let only_regex = args
+            .only_regex
+            .iter()
+            .map(|regex| Regex::new(regex).with_context(|| format!("Invalid --only-regex {regex}")))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let mut config: Config = toml::from_str(&toml).with_context(|| {
-            format!(
-                "Toml in path {} is not formatted correctly",
-                args.path.display()
-            )
-        })?;
+        let selected = config
+            .requests
+            .iter()
+            .enumerate()
+            .filter(|(_, request)| {
+                ((only.is_empty() && only_regex.is_empty())
+                    || only.iter().any(|pattern| pattern.matches(&request.name))
+                    || only_regex.iter().any(|regex| regex.is_match(&request.name)))
+                    && (args.tag.is_empty()
+                        || args.tag.iter().any(|tag| request.tags.contains(tag)))
+                    && !args
+                        .exclude_tag
+                        .iter()
+                        .any(|tag| request.tags.contains(tag))
+            })
+            .map(|(index, _)| index)
+            .skip(args.skip)
+            .take(args.take.unwrap_or(usize::MAX));
 
-        let take = args.take.unwrap_or(config.requests.len());
+        // Selected requests can't run without the requests extracting their captures.
+        let kept = captures::with_providers(&config.requests, selected)?;
         config.requests = config
             .requests
             .into_iter()
-            .skip(args.skip)
-            .take(take)
+            .enumerate()
+            .filter(|(index, _)| kept.contains(index))
+            .map(|(_, request)| request)
             .collect();
 
+        config.sequential |= args.sequential;
+
         for request_config in &mut config.requests {
             if args.skip_ignore {
-                request_config.left.ignore_lines = vec![];
-                request_config.right.ignore_lines = vec![];
-            } else {
-                request_config
-                    .left
-                    .ignore_lines
-                    .extend(config.ignore_lines.clone());
-
-                request_config
-                    .right
-                    .ignore_lines
-                    .extend(config.ignore_lines.clone());
+                for part in [&mut request_config.left, &mut request_config.right] {
+                    part.ignore_lines.clear();
+                    part.ignore_paths.clear();
+                }
+            }
+
+            if args.status_only {
+                request_config.status_only = true;
             }
 
-            if args.all_cache {
-                request_config.left.cached = true;
-                request_config.right.cached = true;
+            if args.all_cache || args.snapshot.is_some() {
+                request_config.left.cached = Some(true);
+                request_config.right.cached = Some(true);
             } else if args.no_cache {
-                request_config.left.cached = false;
-                request_config.right.cached = false;
+                request_config.left.cached = Some(false);
+                request_config.right.cached = Some(false);
             }
         }
 
@@ -122,29 +304,33 @@ impl TryFrom<&Args> for Config {
     }
 }
 
-fn process_env_variables(str: &str, envs: &HashMap<String, String>) -> String {
-    let mut chars = str.chars();
-    let mut replacement: Vec<char> = vec![];
-    while let Some(c) = chars.next() {
-        if c == '$' {
-            if let Some(next_char) = chars.next() {
-                if next_char == '{' {
-                    let env_variable: String =
-                        chars.by_ref().take_while(|&char| char != '}').collect();
-                    let new_val: String = envs.get(&env_variable).unwrap_or_else(|| panic!(
-                        "Env variable {env_variable}, is not found. Make sure to provide it or add it to `.env`"
-                    )).clone();
-
-                    replacement.extend(new_val.chars());
-                } else {
-                    replacement.push(c);
-                    replacement.push(next_char);
-                }
-            }
-        } else {
-            replacement.push(c);
-        }
-    }
+#[derive(clap::Args, Debug)]
+pub struct ImportArgs {
+    /// Path of a HAR export, every entry becomes a request
+    #[arg(
+        long = "har",
+        required_unless_present = "curl",
+        conflicts_with = "curl"
+    )]
+    pub har: Option<PathBuf>,
+
+    /// Path of a file of curl commands, one per line or continued with a trailing `\`
+    #[arg(long = "curl")]
+    pub curl: Option<PathBuf>,
+
+    /// Build the right side by replacing a host of the left side, as `FROM=TO` such as
+    /// `staging.example.com=example.com:8443`. The right side is a copy of the left one otherwise
+    #[arg(long = "map-host", value_name = "FROM=TO")]
+    pub map_host: Vec<String>,
+
+    /// Write the config to this path instead of stdout
+    #[arg(short = 'o', long = "out")]
+    pub out: Option<PathBuf>,
+}
 
-    replacement.iter().collect()
+#[derive(clap::Args, Debug)]
+pub struct CheckArgs {
+    /// Paths of the toml configs to validate, along with the configs they include
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
 }
@@ -0,0 +1,100 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+
+use super::request::ClientCredentials;
+
+/// Tokens are refreshed this long before they expire so they don't expire mid request.
+const REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+/// OAuth access tokens shared by all requests of a run, fetched on first use and refreshed once
+/// they are about to expire.
+#[derive(Default)]
+pub struct Tokens {
+    tokens: Mutex<HashMap<String, Token>>,
+}
+
+struct Token {
+    access_token: String,
+    expires_at: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+impl Tokens {
+    pub async fn client_credentials(
+        &self,
+        reqwest: &reqwest::Client,
+        credentials: &ClientCredentials,
+    ) -> Result<String> {
+        let key = format!(
+            "{} {} {}",
+            credentials.token_url,
+            credentials.client_id,
+            credentials.scopes.join(" ")
+        );
+
+        // The lock is held while fetching so concurrent requests wait for a single new token.
+        let mut tokens = self.tokens.lock().await;
+        if let Some(token) = tokens.get(&key)
+            && token
+                .expires_at
+                .is_none_or(|expires_at| expires_at > Instant::now() + REFRESH_MARGIN)
+        {
+            return Ok(token.access_token.clone());
+        }
+
+        let token = Self::fetch(reqwest, credentials).await?;
+        let access_token = token.access_token.clone();
+        tokens.insert(key, token);
+        Ok(access_token)
+    }
+
+    async fn fetch(reqwest: &reqwest::Client, credentials: &ClientCredentials) -> Result<Token> {
+        let mut form = vec![("grant_type", "client_credentials".to_string())];
+        if !credentials.scopes.is_empty() {
+            form.push(("scope", credentials.scopes.join(" ")));
+        }
+
+        let start = Instant::now();
+        let response = reqwest
+            .post(&credentials.token_url)
+            .basic_auth(&credentials.client_id, Some(&credentials.client_secret))
+            .form(&form)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .with_context(|| {
+                format!(
+                    "Failed requesting OAuth2 token from {}",
+                    credentials.token_url
+                )
+            })?
+            .json::<TokenResponse>()
+            .await
+            .with_context(|| {
+                format!(
+                    "Invalid OAuth2 token response from {}",
+                    credentials.token_url
+                )
+            })?;
+
+        Ok(Token {
+            access_token: response.access_token,
+            expires_at: response
+                .expires_in
+                .map(|expires_in| start + Duration::from_secs(expires_in)),
+        })
+    }
+}
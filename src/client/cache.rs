@@ -0,0 +1,70 @@
+use std::{collections::HashMap, io::Read};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{request::PartRequestConfig, response::PartResponse};
+
+const CACHE_VERSION: u32 = 2;
+
+/// Cached responses keyed by request fingerprint. Entries of cache files written before
+/// fingerprints existed are kept in `legacy` and moved to their fingerprint the first time a
+/// request finds them.
+#[derive(Deserialize, Serialize)]
+pub struct Cache {
+    version: u32,
+    responses: HashMap<String, PartResponse>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    legacy: HashMap<String, PartResponse>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CacheFile {
+    Versioned(Cache),
+    Legacy(HashMap<String, PartResponse>),
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self {
+            version: CACHE_VERSION,
+            responses: HashMap::new(),
+            legacy: HashMap::new(),
+        }
+    }
+}
+
+impl Cache {
+    /// Reads a cache file in either format, an empty or unreadable file results in an empty cache.
+    pub fn from_reader(reader: impl Read) -> Self {
+        match serde_json::from_reader(reader) {
+            Ok(CacheFile::Versioned(cache)) => cache,
+            Ok(CacheFile::Legacy(legacy)) => Self {
+                legacy,
+                ..Self::default()
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn get(&mut self, request: &PartRequestConfig) -> Result<Option<PartResponse>> {
+        let fingerprint = request.fingerprint()?;
+        if let Some(response) = self.responses.get(&fingerprint) {
+            return Ok(Some(response.clone()));
+        }
+
+        let Some(response) = self.legacy.remove(&request.legacy_cache_key()?) else {
+            return Ok(None);
+        };
+
+        self.responses.insert(fingerprint, response.clone());
+        Ok(Some(response))
+    }
+
+    pub fn insert(&mut self, request: &PartRequestConfig, response: PartResponse) -> Result<()> {
+        self.responses.insert(request.fingerprint()?, response);
+        Ok(())
+    }
+}
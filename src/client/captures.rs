@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::{Context, Result, anyhow, bail};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+use super::request::{PartRequestConfig, RequestsConfig};
+
+/// Splits the requests into stages that run one after the other, every request comes after the
+/// requests extracting the captures it references. Requests of the same stage run in parallel.
+pub fn stages(requests: &[RequestsConfig]) -> Result<Vec<Vec<RequestsConfig>>> {
+    let dependencies = dependencies(requests)?;
+
+    let mut stage_of: Vec<Option<usize>> = vec![None; requests.len()];
+    while stage_of.iter().any(Option::is_none) {
+        let mut progressed = false;
+        for index in 0..requests.len() {
+            if stage_of[index].is_some() {
+                continue;
+            }
+
+            let stage = dependencies[index]
+                .iter()
+                .map(|&dependency| stage_of[dependency].map(|stage| stage + 1))
+                .try_fold(0, |max, stage| stage.map(|stage| max.max(stage)));
+
+            if stage.is_some() {
+                stage_of[index] = stage;
+                progressed = true;
+            }
+        }
+
+        if !progressed {
+            let names = requests
+                .iter()
+                .zip(&stage_of)
+                .filter(|(_, stage)| stage.is_none())
+                .map(|(request, _)| request.name.as_str())
+                .collect::<Vec<_>>();
+            bail!(
+                "Requests {} depend on each other through their captures",
+                names.join(", ")
+            );
+        }
+    }
+
+    let mut stages = vec![vec![]; stage_of.iter().flatten().max().map_or(0, |max| max + 1)];
+    for (request, stage) in requests.iter().zip(stage_of) {
+        stages[stage.expect("Every request has a stage at this point")].push(request.clone());
+    }
+
+    Ok(stages)
+}
+
+/// Indexes of the selected requests and of the requests extracting the captures they reference,
+/// directly or through the captures of those requests.
+pub fn with_providers(
+    requests: &[RequestsConfig],
+    selected: impl IntoIterator<Item = usize>,
+) -> Result<BTreeSet<usize>> {
+    let dependencies = dependencies(requests)?;
+    let mut kept = BTreeSet::new();
+    let mut pending = selected.into_iter().collect::<Vec<_>>();
+    while let Some(index) = pending.pop() {
+        if kept.insert(index) {
+            pending.extend(&dependencies[index]);
+        }
+    }
+
+    Ok(kept)
+}
+
+/// Indexes of the requests extracting the captures referenced by each request. A capture can be
+/// extracted by both sides of a request but not by several requests, which one runs last would
+/// decide its value.
+fn dependencies(requests: &[RequestsConfig]) -> Result<Vec<BTreeSet<usize>>> {
+    let mut providers = HashMap::<&str, usize>::new();
+    for (index, request) in requests.iter().enumerate() {
+        for name in request
+            .left
+            .extract
+            .keys()
+            .chain(request.right.extract.keys())
+        {
+            if let Some(&provider) = providers.get(name.as_str())
+                && provider != index
+            {
+                bail!(
+                    "Capture {name} is extracted by both requests {} and {}",
+                    requests[provider].name,
+                    request.name
+                );
+            }
+            providers.insert(name, index);
+        }
+    }
+
+    Ok(requests
+        .iter()
+        .enumerate()
+        .map(|(index, request)| {
+            placeholders(&request.left)
+                .union(&placeholders(&request.right))
+                .filter_map(|name| providers.get(name.as_str()))
+                .copied()
+                .filter(|&provider| provider != index)
+                .collect()
+        })
+        .collect())
+}
+
+/// Replaces the `{{name}}` placeholders of the URL, headers, query and body with the captured
+/// values.
+pub fn apply(
+    request: &PartRequestConfig,
+    captures: &HashMap<String, String>,
+) -> Result<PartRequestConfig> {
+    let mut request = request.clone();
+    let url = request.url.clone();
+    visit_strings(&mut request, |text| {
+        *text = substitute(text, captures)
+            .with_context(|| format!("Failed to build request for URL {url}"))?;
+        Ok(())
+    })?;
+
+    Ok(request)
+}
+
+/// Values matched in a JSON body by the JSON paths or JSON Pointers of `extract`. Strings are
+/// captured as is, any other value as its JSON representation.
+pub fn extract(text: &str, extract: &BTreeMap<String, String>) -> Result<HashMap<String, String>> {
+    let value = serde_json::from_str::<Value>(text)
+        .context("Captures can only be extracted from JSON responses")?;
+
+    let mut captures = HashMap::new();
+    for (name, path) in extract {
+        let matched = if path.is_empty() || path.starts_with('/') {
+            value.pointer(path)
+        } else {
+            JsonPath::parse(path)
+                .with_context(|| format!("Invalid JSON path for capture {name}: {path}"))?
+                .query(&value)
+                .first()
+        };
+
+        let extracted = match matched {
+            Some(Value::String(string)) => string.clone(),
+            Some(value) => value.to_string(),
+            None => bail!("Capture {name} did not match anything with {path}"),
+        };
+
+        captures.insert(name.clone(), extracted);
+    }
+
+    Ok(captures)
+}
+
+fn placeholders(request: &PartRequestConfig) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+    let mut request = request.clone();
+    let _ = visit_strings(&mut request, |text| {
+        names.extend(parse(text).into_iter().filter_map(|segment| match segment {
+            Segment::Placeholder(name) => Some(name.to_string()),
+            Segment::Text(_) => None,
+        }));
+        Ok(())
+    });
+
+    names
+}
+
+fn visit_strings(
+    request: &mut PartRequestConfig,
+    mut visit: impl FnMut(&mut String) -> Result<()>,
+) -> Result<()> {
+    visit(&mut request.url)?;
+    for value in request
+        .headers
+        .values_mut()
+        .chain(request.query.values_mut())
+        .chain(request.form.values_mut())
+        .chain(request.body.iter_mut())
+    {
+        visit(value)?;
+    }
+
+    if let Some(json) = &mut request.json {
+        visit_json(json, &mut visit)?;
+    }
+
+    Ok(())
+}
+
+fn visit_json(value: &mut Value, visit: &mut impl FnMut(&mut String) -> Result<()>) -> Result<()> {
+    match value {
+        Value::String(string) => visit(string),
+        Value::Array(array) => array
+            .iter_mut()
+            .try_for_each(|value| visit_json(value, visit)),
+        Value::Object(map) => map
+            .values_mut()
+            .try_for_each(|value| visit_json(value, visit)),
+        _ => Ok(()),
+    }
+}
+
+fn substitute(text: &str, captures: &HashMap<String, String>) -> Result<String> {
+    parse(text)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => Ok(text),
+            Segment::Placeholder(name) => captures.get(name).map(String::as_str).ok_or_else(|| {
+                anyhow!("Capture {name} is not available, no earlier request extracted it")
+            }),
+        })
+        .collect()
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// Splits a text around its `{{name}}` placeholders, braces around anything that is not a
+/// capture name are kept as text.
+fn parse(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+
+        let name = rest[start + 2..start + 2 + length].trim();
+        let end = start + 2 + length + 2;
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+        {
+            segments.push(Segment::Text(&rest[..start + 2]));
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        segments.push(Segment::Text(&rest[..start]));
+        segments.push(Segment::Placeholder(name));
+        rest = &rest[end..];
+    }
+
+    segments.push(Segment::Text(rest));
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::Config;
+
+    /// Requests named after the captures they extract on their left side and reference in their
+    /// left URL.
+    fn requests(specs: &[(&str, &[&str], &str)]) -> Vec<RequestsConfig> {
+        let toml = specs
+            .iter()
+            .map(|(name, extract, path)| {
+                let extract = extract
+                    .iter()
+                    .map(|capture| format!("{capture} = \"$.{capture}\""))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "[[requests]]\nname = \"{name}\"\n[requests.left]\nurl = \"http://a{path}\"\n\
+                     extract = {{ {extract} }}\n[requests.right]\nurl = \"http://b\"\n"
+                )
+            })
+            .collect::<Vec<_>>()
+            .concat();
+        toml::from_str::<Config>(&toml).unwrap().requests
+    }
+
+    fn names(stages: &[Vec<RequestsConfig>]) -> Vec<Vec<&str>> {
+        stages
+            .iter()
+            .map(|stage| stage.iter().map(|request| request.name.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn runs_requests_after_their_providers() {
+        let requests = requests(&[
+            ("profile", &[], "/users/{{id}}?token={{token}}"),
+            ("health", &[], "/health"),
+            ("login", &["token"], "/login"),
+            ("user", &["id"], "/me?token={{token}}"),
+        ]);
+
+        let stages = stages(&requests).unwrap();
+        assert_eq!(
+            names(&stages),
+            [vec!["health", "login"], vec!["user"], vec!["profile"]]
+        );
+    }
+
+    #[test]
+    fn runs_everything_at_once_without_captures() {
+        let requests = requests(&[("a", &[], "/a"), ("b", &[], "/b")]);
+        assert_eq!(names(&stages(&requests).unwrap()), [vec!["a", "b"]]);
+    }
+
+    #[test]
+    fn ignores_captures_a_request_references_itself() {
+        let requests = requests(&[("a", &["page"], "/a?page={{page}}")]);
+        assert_eq!(names(&stages(&requests).unwrap()), [vec!["a"]]);
+    }
+
+    #[test]
+    fn fails_on_cycles() {
+        let requests = requests(&[
+            ("a", &["x"], "/a?y={{y}}"),
+            ("b", &["y"], "/b?x={{x}}"),
+            ("c", &[], "/c"),
+        ]);
+
+        let Err(error) = stages(&requests) else {
+            panic!("Requests depending on each other have no stages");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Requests a, b depend on each other through their captures"
+        );
+    }
+
+    #[test]
+    fn fails_on_captures_extracted_by_several_requests() {
+        let requests = requests(&[("a", &["token"], "/a"), ("b", &["token"], "/b")]);
+
+        let Err(error) = stages(&requests) else {
+            panic!("A capture extracted twice has no single provider");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Capture token is extracted by both requests a and b"
+        );
+    }
+
+    #[test]
+    fn keeps_providers_of_selected_requests() {
+        let requests = requests(&[
+            ("login", &["token"], "/login"),
+            ("user", &["id"], "/me?token={{token}}"),
+            ("health", &[], "/health"),
+            ("profile", &[], "/users/{{id}}"),
+        ]);
+
+        let kept = with_providers(&requests, [3]).unwrap();
+        assert_eq!(kept.into_iter().collect::<Vec<_>>(), [0, 1, 3]);
+    }
+
+    #[test]
+    fn leaves_unknown_braces_as_text() {
+        let captures = HashMap::from([("id".to_string(), "7".to_string())]);
+        let requests = requests(&[("a", &[], "/a/{{id}}?q={{ not a capture }}")]);
+
+        let applied = apply(&requests[0].left, &captures).unwrap();
+        assert_eq!(applied.url, "http://a/a/7?q={{ not a capture }}");
+    }
+}
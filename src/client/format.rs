@@ -0,0 +1,94 @@
+use anyhow::{Context, Result};
+use quick_xml::{Reader, Writer, events::Event};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// How a response body is normalized before being compared.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    Xml,
+    Yaml,
+    Text,
+    Binary,
+}
+
+impl Format {
+    /// Picks the format from a `Content-Type` header, ignoring its parameters such as `charset`.
+    pub fn from_content_type(content_type: &str, body: &[u8]) -> Self {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+
+        match mime.as_str() {
+            "application/json" => Format::Json,
+            m if m.ends_with("+json") => Format::Json,
+            "application/xml" | "text/xml" => Format::Xml,
+            m if m.ends_with("+xml") => Format::Xml,
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => Format::Yaml,
+            m if m.starts_with("text/") => Format::Text,
+            m if m.starts_with("image/")
+                || m.starts_with("audio/")
+                || m.starts_with("video/")
+                || m == "application/octet-stream"
+                || m == "application/pdf"
+                || m == "application/zip" =>
+            {
+                Format::Binary
+            }
+            _ => Self::detect(body),
+        }
+    }
+
+    /// Guesses the format of a body without a usable `Content-Type`.
+    pub fn detect(body: &[u8]) -> Self {
+        if serde_json::from_slice::<Value>(body).is_ok() {
+            Format::Json
+        } else if std::str::from_utf8(body).is_ok() {
+            Format::Text
+        } else {
+            Format::Binary
+        }
+    }
+
+    pub fn normalize(self, body: &[u8]) -> Result<String> {
+        match self {
+            Format::Json => serde_json::from_slice::<Value>(body)
+                .and_then(|value| serde_json::to_string_pretty(&value))
+                .context("Invalid body format, expecting JSON format"),
+            Format::Xml => Self::xml_pretty_format(body),
+            Format::Yaml => serde_yaml::from_slice::<Value>(body)
+                .and_then(|value| serde_yaml::to_string(&value))
+                .context("Invalid body format, expecting YAML format"),
+            Format::Text => Ok(String::from_utf8_lossy(body).into_owned()),
+            Format::Binary => Ok(format!(
+                "Binary body of {} bytes, sha256 {:x}",
+                body.len(),
+                Sha256::digest(body)
+            )),
+        }
+    }
+
+    fn xml_pretty_format(body: &[u8]) -> Result<String> {
+        let mut reader = Reader::from_reader(body);
+        reader.config_mut().trim_text(true);
+        let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+
+        loop {
+            match reader
+                .read_event()
+                .context("Invalid body format, expecting XML format")?
+            {
+                Event::Eof => break,
+                event => writer.write_event(event).context("Failed to format XML")?,
+            }
+        }
+
+        String::from_utf8(writer.into_inner()).context("Formatted XML is not valid UTF-8")
+    }
+}
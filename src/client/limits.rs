@@ -0,0 +1,67 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use reqwest::Url;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Bounds how many request pairs run at once and how often each host is called.
+#[derive(Default)]
+pub struct Limits {
+    concurrency: Option<Semaphore>,
+    rate_interval: Option<Duration>,
+    next_slots: Mutex<HashMap<String, Instant>>,
+}
+
+impl Limits {
+    /// `rate_interval` is the minimum time between two calls to the same host.
+    pub fn new(concurrency: Option<usize>, rate_interval: Option<Duration>) -> Self {
+        Self {
+            concurrency: concurrency.map(|permits| Semaphore::new(permits.max(1))),
+            rate_interval,
+            next_slots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for a free slot when the concurrency is limited, the slot is released once the
+    /// returned permit is dropped.
+    pub async fn acquire(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.concurrency {
+            Some(semaphore) => Some(
+                semaphore
+                    .acquire()
+                    .await
+                    .expect("Semaphore is never closed"),
+            ),
+            None => None,
+        }
+    }
+
+    /// Waits until the host of the URL can be called again without going over the rate limit.
+    pub async fn wait_for_host(&self, url: &str) {
+        let Some(rate_interval) = self.rate_interval else {
+            return;
+        };
+
+        let host = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_default();
+
+        let slot = {
+            let mut next_slots = self.next_slots.lock().unwrap();
+            let now = Instant::now();
+            let slot = next_slots
+                .get(&host)
+                .copied()
+                .filter(|&slot| slot > now)
+                .unwrap_or(now);
+            next_slots.insert(host, slot + rate_interval);
+            slot
+        };
+
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
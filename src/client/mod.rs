@@ -1,122 +1,326 @@
+mod auth;
+mod cache;
+pub mod captures;
+mod format;
+mod limits;
 mod request;
 mod response;
 
 use std::{
-    collections::HashMap,
-    fs::{OpenOptions, create_dir_all},
+    collections::{BTreeMap, HashMap},
+    fs::{File, OpenOptions, create_dir_all},
     io::{BufReader, Write},
     mem,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result, anyhow};
-pub use request::{Config, RequestsConfig};
+pub use captures::stages;
+pub use request::{
+    Auth, BasicAuth, BearerToken, Config, PartDefaults, PartRequestConfig, RequestsConfig,
+    check_retries, check_status, timeout_duration,
+};
 use reqwest::{
-    Method,
+    Method, RequestBuilder,
     header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue, USER_AGENT},
 };
 use response::PartResponse;
 pub use response::Response;
 use serde_json::Value;
+use serde_json_path::{JsonPath, NormalizedPath};
+
+use crate::client::{
+    auth::Tokens,
+    cache::Cache,
+    format::Format,
+    limits::Limits,
+    request::{DEFAULT_RETRY_ON, RequestBody, RetryError, RetryOn},
+};
 
-use crate::client::request::PartRequestConfig;
+const IGNORED_VALUE: &str = "<ignored>";
+/// Timeout of every call without a `timeout` of its own, so a stuck endpoint can't hang a run.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const RETRY_BACKOFF: Duration = Duration::from_millis(250);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
 
 #[derive(Clone)]
 pub struct Client {
     reqwest: reqwest::Client,
-    cache: Arc<Mutex<HashMap<String, PartResponse>>>,
+    cache: Arc<Mutex<Cache>>,
     cache_location: Option<PathBuf>,
+    left_snapshot: Option<Arc<Mutex<Cache>>>,
+    right_snapshot: Option<Arc<Mutex<Cache>>>,
+    left_captures: Arc<Mutex<HashMap<String, String>>>,
+    right_captures: Arc<Mutex<HashMap<String, String>>>,
+    tokens: Arc<Tokens>,
+    limits: Arc<Limits>,
+    sequential: bool,
 }
 
 impl Client {
     pub fn new() -> Self {
         Self {
-            reqwest: reqwest::Client::new(),
-            cache: Arc::new(Mutex::new(HashMap::new())),
+            reqwest: reqwest::Client::builder()
+                .timeout(DEFAULT_TIMEOUT)
+                .build()
+                .expect("Failed to initialize the HTTP client"),
+            cache: Arc::new(Mutex::new(Cache::default())),
             cache_location: None,
+            left_snapshot: None,
+            right_snapshot: None,
+            left_captures: Arc::new(Mutex::new(HashMap::new())),
+            right_captures: Arc::new(Mutex::new(HashMap::new())),
+            tokens: Arc::new(Tokens::default()),
+            limits: Arc::new(Limits::default()),
+            sequential: false,
         }
     }
 
+    /// Limits the number of request pairs running at once and the time between two calls to the
+    /// same host. When `sequential` is set the right side is only sent after the left one.
+    pub fn set_limits(
+        &mut self,
+        concurrency: Option<usize>,
+        rate_interval: Option<Duration>,
+        sequential: bool,
+    ) {
+        self.limits = Arc::new(Limits::new(concurrency, rate_interval));
+        self.sequential = sequential;
+    }
+
     pub async fn get_response(&mut self, request: &RequestsConfig) -> Result<Response> {
-        let (left_response, right_response) =
-            tokio::join!(self.get(&request.left), self.get(&request.right));
+        let left = captures::apply(&request.left, &self.left_captures.lock().unwrap())?;
+        let right = captures::apply(&request.right, &self.right_captures.lock().unwrap())?;
+
+        let _permit = self.limits.acquire().await;
+        let start = Instant::now();
+        let (left_response, right_response) = if self.sequential {
+            let left_response = self.get(&left, self.left_snapshot.as_ref()).await;
+            let right_response = self.get(&right, self.right_snapshot.as_ref()).await;
+            (left_response, right_response)
+        } else {
+            tokio::join!(
+                self.get(&left, self.left_snapshot.as_ref()),
+                self.get(&right, self.right_snapshot.as_ref())
+            )
+        };
 
-        let mut left_response = left_response?;
-        let mut right_response = right_response?;
+        let left_response = left_response?;
+        let right_response = right_response?;
 
-        if request.left.cached || request.right.cached {
+        let cache_left = left.is_cached() && self.left_snapshot.is_none();
+        let cache_right = right.is_cached() && self.right_snapshot.is_none();
+        if cache_left || cache_right {
             {
                 let mut cache = self.cache.lock().unwrap();
-                if request.left.cached {
-                    cache.insert(left_response.url.clone(), left_response.clone());
+                if cache_left {
+                    cache.insert(&left, left_response.clone())?;
                 }
 
-                if request.right.cached {
-                    cache.insert(right_response.url.clone(), right_response.clone());
+                if cache_right {
+                    cache.insert(&right, right_response.clone())?;
                 }
             }
         }
 
+        for (part, response, captures) in [
+            (&left, &left_response, &self.left_captures),
+            (&right, &right_response, &self.right_captures),
+        ] {
+            if !part.extract.is_empty() {
+                let extracted = captures::extract(&response.text, &part.extract)
+                    .with_context(|| format!("Failed to extract captures for {}", request.name))?;
+                captures.lock().unwrap().extend(extracted);
+            }
+        }
+
+        let mut response = Self::compare(request, left_response, right_response)?;
+        response.elapsed = start.elapsed();
+        Ok(response)
+    }
+
+    /// Compares the responses of both sides once the ignore rules of the request are applied. The
+    /// responses are kept as they were received to compare them again or accept the right side.
+    pub fn compare(
+        request: &RequestsConfig,
+        left_response: PartResponse,
+        right_response: PartResponse,
+    ) -> Result<Response> {
+        let raw_left_response = left_response.clone();
+        let raw_right_response = right_response.clone();
+        let mut left_response = left_response;
+        let mut right_response = right_response;
+
+        if !request.left.ignore_paths.is_empty() {
+            left_response.text =
+                Self::remove_paths(&left_response.text, &request.left.ignore_paths).with_context(
+                    || format!("Failed to apply ignore paths for {}", request.name),
+                )?;
+        }
+
+        if !request.right.ignore_paths.is_empty() {
+            right_response.text =
+                Self::remove_paths(&right_response.text, &request.right.ignore_paths)
+                    .with_context(|| {
+                        format!("Failed to apply ignore paths for {}", request.name)
+                    })?;
+        }
+
         if !request.left.ignore_lines.is_empty() {
             left_response.text = Self::filter(&left_response.text, &request.left.ignore_lines);
         }
 
         if !request.right.ignore_lines.is_empty() {
-            right_response.text = Self::filter(&right_response.text, &request.left.ignore_lines);
+            right_response.text = Self::filter(&right_response.text, &request.right.ignore_lines);
+        }
+
+        let mut response = Response::new(request, left_response, right_response);
+        response.raw_left = Some(raw_left_response);
+        response.raw_right = Some(raw_right_response);
+        Ok(response)
+    }
+
+    /// Accepts the right side of a response as the new expected left side, by storing it in the
+    /// cache under the key of the request's left side. Only a cached left side serves it from then
+    /// on, so requests whose left side isn't cached can't be accepted.
+    pub fn accept(&self, request: &RequestsConfig, response: &Response) -> Result<()> {
+        if self.cache_location.is_none() {
+            return Err(anyhow!("No cache is loaded to accept responses into"));
+        }
+        if !request.left.is_cached() {
+            return Err(anyhow!(
+                "The left side of {} is not cached, an accepted response would never be used",
+                request.name
+            ));
         }
 
-        Ok(Response::new(
-            request.name.clone(),
-            left_response,
-            right_response,
-        ))
+        let mut accepted = response
+            .raw_right
+            .clone()
+            .with_context(|| format!("No right response to accept for {}", request.name))?;
+        let left = captures::apply(&request.left, &self.left_captures.lock().unwrap())?;
+        accepted.url.clone_from(&left.url);
+        self.cache.lock().unwrap().insert(&left, accepted)
     }
 
-    async fn get(&self, request: &PartRequestConfig) -> Result<PartResponse> {
-        if request.cached
-            && let Some(response) = self.cache.lock().unwrap().get(&request.url)
+    async fn get(
+        &self,
+        request: &PartRequestConfig,
+        snapshot: Option<&Arc<Mutex<Cache>>>,
+    ) -> Result<PartResponse> {
+        if let Some(snapshot) = snapshot {
+            let mut response = snapshot
+                .lock()
+                .unwrap()
+                .get(request)?
+                .ok_or_else(|| anyhow!("No response for URL {} in snapshot", request.url))?;
+            response.cached = true;
+            return Ok(response);
+        }
+
+        if request.is_cached()
+            && let Some(mut response) = self.cache.lock().unwrap().get(request)?
         {
-            return Ok(response.clone());
+            response.cached = true;
+            return Ok(response);
         }
 
         self.get_from_url(request).await
     }
 
+    /// Fetches the request, retrying failed attempts with an exponential backoff while they
+    /// match one of its `retry_on` conditions and it has retries left.
     async fn get_from_url(&self, part_request: &PartRequestConfig) -> Result<PartResponse> {
-        let method = part_request
-            .method
+        let retries = part_request.retries.unwrap_or(0);
+        let retry_on = part_request
+            .retry_on
             .as_deref()
-            .unwrap_or("GET")
+            .unwrap_or(&DEFAULT_RETRY_ON);
+
+        let mut attempt = 1;
+        loop {
+            self.limits.wait_for_host(&part_request.url).await;
+            let result = self.fetch(part_request).await;
+            let should_retry = attempt <= retries
+                && match &result {
+                    Ok(response) => retry_on.contains(&RetryOn::Status(response.status_code)),
+                    Err(e) => Self::is_retryable_error(e, retry_on),
+                };
+
+            if !should_retry {
+                return match result {
+                    Ok(mut response) => {
+                        response.attempts = attempt;
+                        Ok(response)
+                    }
+                    Err(e) if attempt > 1 => Err(e.context(format!(
+                        "Failed after {attempt} attempts for URL {}",
+                        part_request.url
+                    ))),
+                    Err(e) => Err(e),
+                };
+            }
+
+            let backoff = RETRY_BACKOFF.saturating_mul(2u32.saturating_pow(attempt - 1));
+            tokio::time::sleep(backoff.min(MAX_RETRY_BACKOFF)).await;
+            attempt += 1;
+        }
+    }
+
+    fn is_retryable_error(error: &anyhow::Error, retry_on: &[RetryOn]) -> bool {
+        let Some(error) = error.downcast_ref::<reqwest::Error>() else {
+            return false;
+        };
+
+        if error.is_timeout() {
+            retry_on.contains(&RetryOn::Error(RetryError::Timeout))
+        } else {
+            (error.is_connect() || error.is_request() || error.is_body())
+                && retry_on.contains(&RetryOn::Error(RetryError::Connection))
+        }
+    }
+
+    async fn fetch(&self, part_request: &PartRequestConfig) -> Result<PartResponse> {
+        let method = part_request.method.as_deref().unwrap_or("GET");
+        let method = method
             .parse::<Method>()
-            .map_err(|_| {
-                anyhow!(
-                    "Unrecognized method {}",
-                    part_request
-                        .method
-                        .as_ref()
-                        .expect("Method should exist at this point")
-                )
-            })?;
+            .with_context(|| format!("Unrecognized method {method}"))?;
 
         let mut request = self.reqwest.request(method, &part_request.url);
 
-        if let Some(basic_auth) = &part_request.basic_auth {
-            request = request.basic_auth(&basic_auth.username, basic_auth.password.clone());
-        }
+        request = match part_request.body()? {
+            Some(RequestBody::Raw(body)) => request.body(body),
+            Some(RequestBody::Json(json)) => request.json(&json),
+            Some(RequestBody::Form(form)) => request.form(&form),
+            None => request,
+        };
 
-        let headers = part_request
-            .headers
-            .iter()
-            .map(|(k, v)| {
-                (
-                    HeaderName::from_bytes(k.as_bytes())
-                        .expect("Header contains invalid UTF-8 characters"),
-                    HeaderValue::from_str(v).expect("Header value is not valid"),
+        request = self.authorize(request, part_request).await?;
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &part_request.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes()).with_context(|| {
+                format!("Invalid header name {name:?} for URL {}", part_request.url)
+            })?;
+            let header_value = HeaderValue::from_str(value).with_context(|| {
+                format!(
+                    "Invalid value of header {name} for URL {}",
+                    part_request.url
                 )
-            })
-            .collect::<HeaderMap>();
+            })?;
+            headers.append(header_name, header_value);
+        }
+
+        if let Some(timeout) = part_request
+            .timeout_duration()
+            .with_context(|| format!("Invalid request to URL {}", part_request.url))?
+        {
+            request = request.timeout(timeout);
+        }
 
+        let start = Instant::now();
         let response = request
             .header(USER_AGENT, "Curpare/1.0")
             .headers(headers)
@@ -125,39 +329,75 @@ impl Client {
             .await
             .with_context(|| format!("Failed sending request to URL {}", part_request.url))?;
 
+        let time_to_first_byte = start.elapsed();
         let status_code = response.status();
+        let mut headers = BTreeMap::<String, String>::new();
+        for (name, value) in response.headers() {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            headers
+                .entry(name.as_str().to_string())
+                .and_modify(|existing| {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                })
+                .or_insert_with(|| value.into_owned());
+        }
 
         let content_type = response
             .headers()
             .get(CONTENT_TYPE)
-            .cloned()
-            .ok_or_else(|| anyhow!("CONTENT_TYPE header not found"))?;
+            .and_then(|content_type| content_type.to_str().ok())
+            .map(str::to_string);
 
-        let content_type = content_type.to_str().map_err(|e| anyhow::anyhow!(e))?;
+        let body = response
+            .bytes()
+            .await
+            .with_context(|| format!("Failed reading body of URL {}", part_request.url))?;
+        let elapsed = start.elapsed();
+
+        let format = part_request.format.unwrap_or_else(|| match &content_type {
+            Some(content_type) => Format::from_content_type(content_type, &body),
+            None => Format::detect(&body),
+        });
+
+        let text = format.normalize(&body).with_context(|| {
+            format!(
+                "Failed to format {format:?} response for URL: {}",
+                part_request.url
+            )
+        })?;
+
+        let mut part_response =
+            PartResponse::new(part_request.url.clone(), status_code, headers, text);
+        part_response.elapsed = elapsed;
+        part_response.time_to_first_byte = time_to_first_byte;
+        Ok(part_response)
+    }
 
-        let mut text = response.text().await.map_err(|e| anyhow::anyhow!(e))?;
-        text = match content_type {
-            ct if ct.starts_with("application/json") => Self::json_pretty_format(&text)
-                .with_context(|| format!("Failed to format JSON for URL: {}", part_request.url))?,
-            ct => {
+    /// Adds the credentials of `basic_auth` or `auth` to the request.
+    async fn authorize(
+        &self,
+        request: RequestBuilder,
+        part_request: &PartRequestConfig,
+    ) -> Result<RequestBuilder> {
+        Ok(match (&part_request.basic_auth, &part_request.auth) {
+            (Some(_), Some(_)) => {
                 return Err(anyhow!(
-                    "Could not format response of content_type: {ct}\nURL: {}",
+                    "Only one of `basic_auth` or `auth` can be set for URL {}",
                     part_request.url
                 ));
             }
-        };
-
-        Ok(PartResponse::new(
-            part_request.url.clone(),
-            status_code,
-            text,
-        ))
-    }
-
-    fn json_pretty_format(text: &str) -> Result<String> {
-        serde_json::from_str::<Value>(text)
-            .and_then(|value| serde_json::to_string_pretty(&value))
-            .context("Invalid body format, expecting JSON format")
+            (Some(basic_auth), None) | (None, Some(Auth::Basic(basic_auth))) => {
+                request.basic_auth(&basic_auth.username, basic_auth.password.clone())
+            }
+            (None, Some(Auth::Bearer(bearer))) => request.bearer_auth(bearer.token()?),
+            (None, Some(Auth::Oauth2ClientCredentials(credentials))) => request.bearer_auth(
+                self.tokens
+                    .client_credentials(&self.reqwest, credentials)
+                    .await?,
+            ),
+            (None, None) => request,
+        })
     }
 
     fn filter(text: &str, ignore_list: &[String]) -> String {
@@ -171,6 +411,54 @@ impl Client {
         !ignore_lines.iter().any(|ignore| line.contains(ignore))
     }
 
+    /// Removes every value matched by a JSON path (`$.data.items[*].updated_at`) or a JSON Pointer
+    /// (`/data/items/0/updated_at`). Object members are removed, array elements are masked instead
+    /// so the indices of their siblings don't shift. Bodies that are not JSON are left untouched.
+    fn remove_paths(text: &str, ignore_paths: &[String]) -> Result<String> {
+        let Ok(mut value) = serde_json::from_str::<Value>(text) else {
+            return Ok(text.to_string());
+        };
+
+        let mut pointers = vec![];
+        for path in ignore_paths {
+            if path.is_empty() || path.starts_with('/') {
+                pointers.push(path.clone());
+            } else {
+                let json_path = JsonPath::parse(path)
+                    .with_context(|| format!("Invalid JSONPath in ignore paths: {path}"))?;
+                pointers.extend(
+                    json_path
+                        .query_located(&value)
+                        .locations()
+                        .map(NormalizedPath::to_json_pointer),
+                );
+            }
+        }
+
+        for pointer in pointers {
+            let Some((parent, token)) = pointer.rsplit_once('/') else {
+                continue;
+            };
+
+            let token = token.replace("~1", "/").replace("~0", "~");
+            match value.pointer_mut(parent) {
+                Some(Value::Object(map)) => {
+                    map.remove(&token);
+                }
+                Some(Value::Array(array)) => {
+                    if let Some(element) =
+                        token.parse::<usize>().ok().and_then(|i| array.get_mut(i))
+                    {
+                        *element = Value::String(IGNORED_VALUE.to_string());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        serde_json::to_string_pretty(&value).context("Failed to format JSON after ignoring paths")
+    }
+
     pub fn load_cache(&mut self, cache_location: PathBuf) -> Result<()> {
         if let Some(parent) = cache_location.parent() {
             let _ = create_dir_all(parent).with_context(|| {
@@ -195,13 +483,29 @@ impl Client {
             })?;
 
         let reader = BufReader::new(&file);
-        let cache: HashMap<String, PartResponse> =
-            serde_json::from_reader(reader).unwrap_or_default();
+        let cache = Cache::from_reader(reader);
 
         self.cache = Arc::new(Mutex::new(cache));
         self.cache_location = Some(cache_location);
         Ok(())
     }
+
+    /// Serves the left and right sides from the snapshots at the given locations instead of
+    /// fetching them. A side without a snapshot is fetched as usual.
+    pub fn load_snapshots(&mut self, left: Option<&Path>, right: Option<&Path>) -> Result<()> {
+        let load = |location: &Path| -> Result<Arc<Mutex<Cache>>> {
+            let file = File::open(location).with_context(|| {
+                format!("Failed to open snapshot for path {}", location.display())
+            })?;
+            Ok(Arc::new(Mutex::new(Cache::from_reader(BufReader::new(
+                file,
+            )))))
+        };
+
+        self.left_snapshot = left.map(load).transpose()?;
+        self.right_snapshot = right.map(load).transpose()?;
+        Ok(())
+    }
 }
 
 impl Drop for Client {
@@ -211,7 +515,7 @@ impl Drop for Client {
         }
 
         let cache_location = self.cache_location.take().unwrap();
-        let cache = mem::replace(&mut self.cache, Arc::new(Mutex::new(HashMap::new())));
+        let cache = mem::replace(&mut self.cache, Arc::new(Mutex::new(Cache::default())));
         let cache = Arc::try_unwrap(cache)
             .ok()
             .expect("There should only be one reference to cache at this point")
@@ -241,3 +545,40 @@ impl Drop for Client {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(text: &str) -> PartResponse {
+        PartResponse::new(
+            "http://a".to_string(),
+            reqwest::StatusCode::OK,
+            BTreeMap::new(),
+            text.to_string(),
+        )
+    }
+
+    #[test]
+    fn compares_with_the_ignore_rules_and_keeps_the_raw_responses() {
+        let mut request: RequestsConfig = toml::from_str(
+            "name = \"a\"\n[left]\nurl = \"http://a\"\n\
+             [right]\nurl = \"http://b\"",
+        )
+        .unwrap();
+        request.left.ignore_lines = vec!["date".to_string()];
+        request.right.ignore_lines = vec!["date".to_string()];
+
+        let response =
+            Client::compare(&request, part("id: 1\ndate: 1"), part("id: 1\ndate: 2")).unwrap();
+        assert!(response.is_equal());
+        assert_eq!(response.raw_left.unwrap().text, "id: 1\ndate: 1");
+        assert_eq!(response.raw_right.unwrap().text, "id: 1\ndate: 2");
+
+        request.left.ignore_lines.clear();
+        request.right.ignore_lines.clear();
+        let response =
+            Client::compare(&request, part("id: 1\ndate: 1"), part("id: 1\ndate: 2")).unwrap();
+        assert!(!response.is_equal());
+    }
+}
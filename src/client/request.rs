@@ -1,35 +1,133 @@
+use anyhow::{Context, Result, anyhow};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
+use std::path::PathBuf;
+use std::time::Duration;
 
-#[derive(Deserialize, Serialize)]
+use super::format::Format;
+
+/// Request headers that don't identify a request and are left out of its fingerprint.
+const FINGERPRINT_EXCLUDED_HEADERS: [&str; 3] = ["authorization", "proxy-authorization", "cookie"];
+
+/// Most retries of a side, more of them with the backoff between each keep a run waiting on a
+/// failing upstream for minutes.
+pub const MAX_RETRIES: u32 = 10;
+
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
-    #[serde(default)]
+    /// Variables referenced by `${NAME}` placeholders, already expanded by the time the
+    /// configuration is parsed.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub vars: HashMap<String, String>,
+
+    /// Glob patterns of other configurations whose requests are added to this one, relative to
+    /// this configuration.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ignore_lines: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_paths: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_on: Option<Vec<RetryOn>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sequential: bool,
+
+    #[serde(default, skip_serializing_if = "PartDefaults::is_empty")]
+    pub left: PartDefaults,
+
+    #[serde(default, skip_serializing_if = "PartDefaults::is_empty")]
+    pub right: PartDefaults,
+
+    #[serde(default)]
     pub requests: Vec<RequestsConfig>,
 }
 
 impl Config {
+    /// Minimum time between two calls to the same host, from the `rate_limit` in requests per
+    /// second.
+    pub fn rate_interval(&self) -> Result<Option<Duration>> {
+        self.rate_limit
+            .map(|rate_limit| {
+                seconds(rate_limit)
+                    .and_then(|_| seconds(1.0 / rate_limit))
+                    .with_context(|| {
+                    format!(
+                        "Invalid rate_limit {rate_limit:?}, expected a positive number of requests per second"
+                    )
+                })
+            })
+            .transpose()
+    }
+
     pub fn requires_cache(&self) -> bool {
         self.requests
             .iter()
-            .any(|r| r.left.cached || r.right.cached)
+            .any(|r| r.left.is_cached() || r.right.is_cached())
     }
 }
 
 impl From<Vec<RequestsConfig>> for Config {
     fn from(requests: Vec<RequestsConfig>) -> Self {
         Config {
-            ignore_lines: vec![],
             requests,
+            ..Config::default()
         }
     }
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct RequestsConfig {
     pub name: String,
+
+    /// Labels selecting this request with `--tag` and `--exclude-tag`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub status_only: bool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub compare_headers: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_headers: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_lines: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_paths: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_latency_ratio: Option<f64>,
+
+    /// URL shared by both sides, joined to the `base_url` of each side.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    #[serde(default)]
     pub left: PartRequestConfig,
+
+    #[serde(default)]
     pub right: PartRequestConfig,
 }
 
@@ -39,28 +137,366 @@ impl Display for RequestsConfig {
     }
 }
 
-#[derive(Clone, Deserialize, Serialize)]
+/// Options of the left or right side inherited by every request, which can override them.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PartDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub query: HashMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<Auth>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached: Option<bool>,
+}
+
+impl PartDefaults {
+    /// Fills in what these defaults don't set themselves from the defaults of the configuration
+    /// including them.
+    pub fn inherit(&mut self, parent: &PartDefaults) {
+        self.base_url = self.base_url.take().or_else(|| parent.base_url.clone());
+
+        for (name, value) in &parent.headers {
+            if !self
+                .headers
+                .keys()
+                .any(|key| key.eq_ignore_ascii_case(name))
+            {
+                self.headers.insert(name.clone(), value.clone());
+            }
+        }
+
+        for (key, value) in &parent.query {
+            self.query
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        self.auth = self.auth.take().or_else(|| parent.auth.clone());
+        self.cached = self.cached.or(parent.cached);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.base_url.is_none()
+            && self.headers.is_empty()
+            && self.query.is_empty()
+            && self.auth.is_none()
+            && self.cached.is_none()
+    }
+}
+
+#[derive(Clone, Default, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct PartRequestConfig {
+    #[serde(default)]
     pub url: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub method: Option<String>,
 
-    #[serde(default)]
-    pub cached: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cached: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expect_status: Option<u16>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<Format>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_on: Option<Vec<RetryOn>>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub basic_auth: Option<BasicAuth>,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<Auth>,
+
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ignore_lines: Vec<String>,
 
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ignore_paths: Vec<String>,
+
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub headers: HashMap<String, String>,
 
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub query: HashMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json: Option<Value>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub form: HashMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_file: Option<PathBuf>,
+
+    /// Values captured from this side's response, by name, referenced as `{{name}}` by the same
+    /// side of later requests.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extract: BTreeMap<String, String>,
+}
+
+impl PartRequestConfig {
+    pub fn is_cached(&self) -> bool {
+        self.cached.unwrap_or(false)
+    }
+
+    /// Fills in what this side doesn't set itself from the side's defaults. The URL falls back to
+    /// the request's shared `path` and relative URLs are joined to the `base_url`.
+    pub fn inherit(&mut self, defaults: &PartDefaults, path: Option<&str>) -> Result<()> {
+        if self.url.is_empty() {
+            self.url = path
+                .ok_or_else(|| anyhow!("Either `url` or the request's `path` must be set"))?
+                .to_string();
+        }
+
+        if let Some(base_url) = &defaults.base_url
+            && !has_scheme(&self.url)
+        {
+            self.url = format!(
+                "{}/{}",
+                base_url.trim_end_matches('/'),
+                self.url.trim_start_matches('/')
+            );
+        }
+
+        for (name, value) in &defaults.headers {
+            if !self
+                .headers
+                .keys()
+                .any(|key| key.eq_ignore_ascii_case(name))
+            {
+                self.headers.insert(name.clone(), value.clone());
+            }
+        }
+
+        for (key, value) in &defaults.query {
+            self.query
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        if self.auth.is_none() && self.basic_auth.is_none() {
+            self.auth.clone_from(&defaults.auth);
+        }
+
+        self.cached = self.cached.or(defaults.cached);
+        Ok(())
+    }
+
+    /// Timeout of this side, failing when it isn't a positive number of seconds.
+    pub fn timeout_duration(&self) -> Result<Option<Duration>> {
+        timeout_duration(self.timeout)
+    }
+
+    /// Fails when the timeout, retries or expected status of this side is out of range.
+    pub fn check_ranges(&self) -> Result<()> {
+        self.timeout_duration()?;
+        check_retries(self.retries)?;
+        check_status(self.expect_status)
+    }
+
+    /// Resolves the single body source configured for this request, if any.
+    pub fn body(&self) -> Result<Option<RequestBody>> {
+        let sources = [
+            self.body.is_some(),
+            self.json.is_some(),
+            !self.form.is_empty(),
+            self.body_file.is_some(),
+        ];
+
+        if sources.iter().filter(|&&set| set).count() > 1 {
+            return Err(anyhow!(
+                "Only one of `body`, `json`, `form` or `body_file` can be set for URL {}",
+                self.url
+            ));
+        }
+
+        if let Some(body) = &self.body {
+            return Ok(Some(RequestBody::Raw(body.clone())));
+        }
+
+        if let Some(json) = &self.json {
+            return Ok(Some(RequestBody::Json(json.clone())));
+        }
+
+        if !self.form.is_empty() {
+            return Ok(Some(RequestBody::Form(self.form.clone())));
+        }
+
+        if let Some(path) = &self.body_file {
+            let body = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read body file {}", path.display()))?;
+            return Ok(Some(RequestBody::Raw(body)));
+        }
+
+        Ok(None)
+    }
+
+    /// Canonical fingerprint used to store this request's response in the cache. It is made of
+    /// the method and URL followed by a hash of the sorted query, the sorted headers and the body,
+    /// so requests to the same URL that differ in any of them don't share a cached response.
+    /// Credential headers are left out so rotating tokens keep hitting the cache.
+    pub fn fingerprint(&self) -> Result<String> {
+        let method = self.method.as_deref().unwrap_or("GET").to_uppercase();
+
+        let mut query = self.query.iter().collect::<Vec<_>>();
+        query.sort();
+
+        let mut headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.to_lowercase(), value))
+            .filter(|(name, _)| !FINGERPRINT_EXCLUDED_HEADERS.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        headers.sort();
+
+        let mut hasher = Sha256::new();
+        for (key, value) in query {
+            hasher.update(format!("query {key}={value}\n"));
+        }
+
+        for (name, value) in headers {
+            hasher.update(format!("header {name}: {value}\n"));
+        }
+
+        if let Some(body) = self.body()? {
+            hasher.update(b"body ");
+            hasher.update(Sha256::digest(body.to_bytes()?));
+        }
+
+        Ok(format!("{method} {} {:x}", self.url, hasher.finalize()))
+    }
+
+    /// Key used by cache files written before fingerprints, only the URL and the body.
+    pub fn legacy_cache_key(&self) -> Result<String> {
+        let Some(body) = self.body()? else {
+            return Ok(self.url.clone());
+        };
+
+        let hash = Sha256::digest(body.to_bytes()?);
+        Ok(format!("{}#body:{hash:x}", self.url))
+    }
+}
+
+/// Whether a URL starts with a scheme such as `https://`, and so isn't joined to a `base_url`. A
+/// URL in the query of a relative one, as in `/login?next=https://example.com`, doesn't count.
+fn has_scheme(url: &str) -> bool {
+    url.split_once("://").is_some_and(|(scheme, _)| {
+        let mut chars = scheme.chars();
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+/// Duration of a number of seconds from the configuration, which must be positive and small
+/// enough to be represented.
+pub fn seconds(seconds: f64) -> Result<Duration> {
+    if seconds.is_nan() || seconds <= 0.0 {
+        return Err(anyhow!("{seconds:?} is not a positive number"));
+    }
+
+    Duration::try_from_secs_f64(seconds).with_context(|| format!("{seconds:?} is too large"))
+}
+
+/// Duration of a `timeout`, failing when it isn't a positive number of seconds.
+pub fn timeout_duration(timeout: Option<f64>) -> Result<Option<Duration>> {
+    timeout
+        .map(|timeout| {
+            seconds(timeout).with_context(|| {
+                format!("Invalid timeout {timeout:?}, expected a positive number of seconds")
+            })
+        })
+        .transpose()
+}
+
+pub fn check_retries(retries: Option<u32>) -> Result<()> {
+    match retries {
+        Some(retries) if retries > MAX_RETRIES => Err(anyhow!(
+            "Invalid retries {retries}, expected at most {MAX_RETRIES}"
+        )),
+        _ => Ok(()),
+    }
+}
+
+pub fn check_status(status: Option<u16>) -> Result<()> {
+    match status {
+        Some(status) if !(100..=599).contains(&status) => Err(anyhow!(
+            "Invalid expect_status {status}, expected a status code between 100 and 599"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// A condition under which a failed attempt is retried, either a response status code or a kind
+/// of error.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum RetryOn {
+    Status(u16),
+    Error(RetryError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RetryError {
+    Connection,
+    Timeout,
+}
+
+/// Retry conditions used when `retries` is set without `retry_on`.
+pub const DEFAULT_RETRY_ON: [RetryOn; 5] = [
+    RetryOn::Error(RetryError::Connection),
+    RetryOn::Error(RetryError::Timeout),
+    RetryOn::Status(502),
+    RetryOn::Status(503),
+    RetryOn::Status(504),
+];
+
+pub enum RequestBody {
+    Raw(String),
+    Json(Value),
+    Form(HashMap<String, String>),
+}
+
+impl RequestBody {
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        match self {
+            RequestBody::Raw(body) => Ok(body.as_bytes().to_vec()),
+            RequestBody::Json(json) => {
+                serde_json::to_vec(json).context("Failed to serialize JSON body")
+            }
+            RequestBody::Form(form) => {
+                let mut pairs = form.iter().collect::<Vec<_>>();
+                pairs.sort();
+                Ok(pairs
+                    .iter()
+                    .map(|(k, v)| format!("{k}={v}"))
+                    .collect::<Vec<_>>()
+                    .join("&")
+                    .into_bytes())
+            }
+        }
+    }
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -71,3 +507,198 @@ pub struct BasicAuth {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
 }
+
+/// How a request authenticates, selected by its `type`.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+    Basic(BasicAuth),
+    Bearer(BearerToken),
+    Oauth2ClientCredentials(ClientCredentials),
+}
+
+/// A static bearer token, given as is or read from an environment variable or a file.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BearerToken {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_env: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<PathBuf>,
+}
+
+impl BearerToken {
+    /// Resolves the single token source, a token file is read every time so it can be rotated
+    /// during a run.
+    pub fn token(&self) -> Result<String> {
+        match (&self.token, &self.token_env, &self.token_file) {
+            (Some(token), None, None) => Ok(token.clone()),
+            (None, Some(name), None) => std::env::var(name)
+                .with_context(|| format!("Environment variable {name} of bearer token is not set")),
+            (None, None, Some(path)) => std::fs::read_to_string(path)
+                .map(|token| token.trim().to_string())
+                .with_context(|| format!("Failed to read bearer token file {}", path.display())),
+            _ => Err(anyhow!(
+                "Exactly one of `token`, `token_env` or `token_file` must be set for bearer auth"
+            )),
+        }
+    }
+}
+
+/// Credentials exchanged for an access token with the OAuth client credentials grant.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientCredentials {
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(toml: &str) -> PartRequestConfig {
+        toml::from_str(&format!("url = \"http://a/users\"\n{toml}")).unwrap()
+    }
+
+    fn fingerprint(toml: &str) -> String {
+        part(toml).fingerprint().unwrap()
+    }
+
+    #[test]
+    fn fingerprints_are_stable() {
+        // Cache files are keyed by fingerprints, changing them invalidates every cache.
+        assert_eq!(
+            fingerprint("method = \"post\"\nquery = { page = \"2\" }\nbody = \"{}\""),
+            "POST http://a/users 6c1cf294dca9ddd310df651590ac873b68fbdbf58ceb35fd7f83d3b8b3e13efb"
+        );
+    }
+
+    #[test]
+    fn fingerprints_ignore_the_order_of_queries_and_headers() {
+        assert_eq!(
+            fingerprint("query = { a = \"1\", b = \"2\" }\nheaders = { X-A = \"1\", X-B = \"2\" }"),
+            fingerprint("query = { b = \"2\", a = \"1\" }\nheaders = { x-b = \"2\", X-A = \"1\" }")
+        );
+    }
+
+    #[test]
+    fn fingerprints_leave_out_credentials() {
+        let plain = fingerprint("");
+        for headers in [
+            "headers = { Authorization = \"Bearer a\" }",
+            "headers = { authorization = \"Bearer b\" }",
+            "headers = { Proxy-Authorization = \"Basic c\" }",
+            "headers = { Cookie = \"session=d\" }",
+        ] {
+            assert_eq!(fingerprint(headers), plain, "{headers}");
+        }
+    }
+
+    #[test]
+    fn fingerprints_tell_requests_apart() {
+        let fingerprints = [
+            fingerprint(""),
+            fingerprint("method = \"DELETE\""),
+            fingerprint("query = { page = \"2\" }"),
+            fingerprint("headers = { Accept = \"text/csv\" }"),
+            fingerprint("body = \"a\""),
+            fingerprint("json = { a = 1 }"),
+            fingerprint("form = { a = \"1\" }"),
+        ];
+
+        for (index, fingerprint) in fingerprints.iter().enumerate() {
+            assert!(
+                !fingerprints[index + 1..].contains(fingerprint),
+                "{fingerprint}"
+            );
+        }
+    }
+
+    #[test]
+    fn fingerprints_default_to_get() {
+        assert_eq!(fingerprint("method = \"get\""), fingerprint(""));
+        assert!(fingerprint("").starts_with("GET http://a/users "));
+    }
+
+    #[test]
+    fn converts_positive_seconds() {
+        assert_eq!(seconds(0.5).unwrap(), Duration::from_millis(500));
+        assert_eq!(seconds(30.0).unwrap(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn rejects_seconds_that_are_not_a_duration() {
+        for invalid in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e300] {
+            assert!(seconds(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_timeouts() {
+        assert_eq!(part("").timeout_duration().unwrap(), None);
+        assert_eq!(
+            part("timeout = 2").timeout_duration().unwrap(),
+            Some(Duration::from_secs(2))
+        );
+
+        let Err(error) = part("timeout = -1").timeout_duration() else {
+            panic!("A negative timeout is not a duration");
+        };
+        assert_eq!(
+            format!("{error:#}"),
+            "Invalid timeout -1.0, expected a positive number of seconds: -1.0 is not a positive number"
+        );
+    }
+
+    #[test]
+    fn parses_retry_conditions() {
+        let part = part("retries = 2\nretry_on = [500, \"timeout\", \"connection\"]");
+        assert_eq!(part.retries, Some(2));
+        assert_eq!(
+            part.retry_on.unwrap(),
+            [
+                RetryOn::Status(500),
+                RetryOn::Error(RetryError::Timeout),
+                RetryOn::Error(RetryError::Connection),
+            ]
+        );
+        assert!(
+            toml::from_str::<PartRequestConfig>("url = \"http://a\"\nretry_on = [\"dns\"]")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn joins_relative_urls_to_the_base_url() {
+        let defaults = PartDefaults {
+            base_url: Some("https://api.example.com/v1/".to_string()),
+            ..PartDefaults::default()
+        };
+
+        for (url, expected) in [
+            ("/users", "https://api.example.com/v1/users"),
+            (
+                "/login?next=https://example.com",
+                "https://api.example.com/v1/login?next=https://example.com",
+            ),
+            ("http://other/users", "http://other/users"),
+            ("http://{{host}}/users", "http://{{host}}/users"),
+        ] {
+            let mut part = PartRequestConfig {
+                url: url.to_string(),
+                ..PartRequestConfig::default()
+            };
+            part.inherit(&defaults, None).unwrap();
+            assert_eq!(part.url, expected);
+        }
+    }
+}
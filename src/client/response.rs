@@ -1,16 +1,145 @@
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
+use super::request::RequestsConfig;
+
 pub struct Response {
     pub name: String,
     pub left: PartResponse,
     pub right: PartResponse,
+    pub status_only: bool,
+    pub status_errors: Vec<String>,
+    pub header_differences: Vec<HeaderDifference>,
+    pub latency_error: Option<String>,
+    pub elapsed: Duration,
+
+    /// The left response before ignore rules were applied, used to compare it again with other
+    /// rules.
+    pub raw_left: Option<PartResponse>,
+
+    /// The right response before ignore rules were applied, used to accept it into the cache.
+    pub raw_right: Option<PartResponse>,
 }
 
 impl Response {
-    pub fn new(name: String, left: PartResponse, right: PartResponse) -> Self {
-        Self { name, left, right }
+    pub fn new(request: &RequestsConfig, left: PartResponse, right: PartResponse) -> Self {
+        let status_errors = [
+            ("Left", &request.left, &left),
+            ("Right", &request.right, &right),
+        ]
+        .into_iter()
+        .filter_map(|(side, part_request, part_response)| {
+            part_request
+                .expect_status
+                .filter(|&expected| expected != part_response.status_code)
+                .map(|expected| {
+                    format!(
+                        "{side} expected status code {expected} but got {}",
+                        part_response.status_code
+                    )
+                })
+        })
+        .collect();
+
+        let header_differences = HeaderDifference::collect(request, &left.headers, &right.headers);
+
+        // Cached times say nothing about the current latency of a side.
+        let latency_error = request
+            .max_latency_ratio
+            .filter(|_| !left.cached && !right.cached)
+            .and_then(|max_ratio| {
+            let left_elapsed = left.elapsed.as_secs_f64();
+            let ratio = right.elapsed.as_secs_f64() / left_elapsed;
+            (left_elapsed > 0.0 && ratio > max_ratio).then(|| {
+                format!(
+                    "Right took {}ms, {ratio:.2} times the left {}ms, over the maximum ratio of {max_ratio}",
+                    right.elapsed.as_millis(),
+                    left.elapsed.as_millis()
+                )
+            })
+        });
+
+        Self {
+            name: request.name.clone(),
+            left,
+            right,
+            status_only: request.status_only,
+            status_errors,
+            header_differences,
+            latency_error,
+            elapsed: Duration::ZERO,
+            raw_left: None,
+            raw_right: None,
+        }
+    }
+
+    pub fn is_equal(&self) -> bool {
+        self.status_errors.is_empty()
+            && self.header_differences.is_empty()
+            && self.latency_error.is_none()
+            && self.left.status_code == self.right.status_code
+            && (self.status_only || self.left.text == self.right.text)
+    }
+
+    pub fn is_body_equal(&self) -> bool {
+        self.status_only || self.left.text == self.right.text
+    }
+}
+
+/// A response header whose value differs between the two sides, `None` when it is missing.
+pub struct HeaderDifference {
+    pub name: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+impl HeaderDifference {
+    fn collect(
+        request: &RequestsConfig,
+        left: &BTreeMap<String, String>,
+        right: &BTreeMap<String, String>,
+    ) -> Vec<Self> {
+        let compare_all = request.compare_headers.iter().any(|name| name == "*");
+        let mut names = if compare_all {
+            left.keys().chain(right.keys()).cloned().collect::<Vec<_>>()
+        } else {
+            request
+                .compare_headers
+                .iter()
+                .map(|name| name.to_lowercase())
+                .collect()
+        };
+        names.sort();
+        names.dedup();
+
+        names
+            .into_iter()
+            .filter(|name| {
+                !request
+                    .ignore_headers
+                    .iter()
+                    .any(|ignored| ignored.eq_ignore_ascii_case(name))
+            })
+            .filter_map(|name| {
+                let left = left.get(&name).cloned();
+                let right = right.get(&name).cloned();
+                (left != right).then_some(Self { name, left, right })
+            })
+            .collect()
+    }
+}
+
+impl Display for HeaderDifference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "<missing>".into());
+        write!(
+            f,
+            "Header {}: {} => {}",
+            self.name,
+            value(&self.left),
+            value(&self.right)
+        )
     }
 }
 
@@ -18,15 +147,70 @@ impl Response {
 pub struct PartResponse {
     pub url: String,
     pub status_code: u16,
+
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+
     pub text: String,
+
+    /// Number of attempts it took to get this response, more than one when it was retried.
+    #[serde(default = "default_attempts")]
+    pub attempts: u32,
+
+    /// Time from sending the request until the whole body was received.
+    #[serde(default)]
+    pub elapsed: Duration,
+
+    /// Time from sending the request until the response headers were received.
+    #[serde(default)]
+    pub time_to_first_byte: Duration,
+
+    /// Whether it was served from the cache or a snapshot, its times are then those of the
+    /// request that filled it and are left out of latency comparisons.
+    #[serde(skip)]
+    pub cached: bool,
+}
+
+fn default_attempts() -> u32 {
+    1
 }
 
 impl PartResponse {
-    pub fn new(url: String, status_code: reqwest::StatusCode, text: String) -> Self {
+    pub fn new(
+        url: String,
+        status_code: reqwest::StatusCode,
+        headers: BTreeMap<String, String>,
+        text: String,
+    ) -> Self {
         Self {
             url,
             status_code: status_code.into(),
+            headers,
             text,
+            attempts: 1,
+            elapsed: Duration::ZERO,
+            time_to_first_byte: Duration::ZERO,
+            cached: false,
+        }
+    }
+
+    /// Status code along with the time it took, the time to its first byte and the number of
+    /// attempts when the response was retried, or where it was served from when it is cached.
+    pub fn status_label(&self) -> String {
+        if self.cached {
+            return format!("{} from the cache", self.status_code);
+        }
+
+        let label = format!(
+            "{} in {}ms, first byte {}ms",
+            self.status_code,
+            self.elapsed.as_millis(),
+            self.time_to_first_byte.as_millis()
+        );
+        if self.attempts > 1 {
+            format!("{label} after {} attempts", self.attempts)
+        } else {
+            label
         }
     }
 }
@@ -36,3 +220,76 @@ impl Display for PartResponse {
         write!(f, "Status code {}\n{}", self.status_code, self.text)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(toml: &str) -> RequestsConfig {
+        toml::from_str(&format!(
+            "name = \"a\"\n{toml}\n[left]\nurl = \"http://a\"\n[right]\nurl = \"http://b\""
+        ))
+        .unwrap()
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    fn differences(toml: &str) -> Vec<(String, Option<String>, Option<String>)> {
+        let left = headers(&[("etag", "1"), ("cache-control", "no-cache"), ("date", "a")]);
+        let right = headers(&[("etag", "2"), ("cache-control", "no-cache"), ("date", "b")]);
+        HeaderDifference::collect(&request(toml), &left, &right)
+            .into_iter()
+            .map(|difference| (difference.name, difference.left, difference.right))
+            .collect()
+    }
+
+    #[test]
+    fn compares_no_headers_by_default() {
+        assert!(differences("").is_empty());
+    }
+
+    #[test]
+    fn compares_listed_headers_case_insensitively() {
+        assert_eq!(
+            differences("compare_headers = [\"ETag\", \"Cache-Control\", \"X-Missing\"]"),
+            [(
+                "etag".to_string(),
+                Some("1".to_string()),
+                Some("2".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn compares_every_header_but_the_ignored_ones() {
+        assert_eq!(
+            differences("compare_headers = [\"*\"]\nignore_headers = [\"Date\"]"),
+            [(
+                "etag".to_string(),
+                Some("1".to_string()),
+                Some("2".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_headers_missing_on_one_side() {
+        let request = request("compare_headers = [\"*\"]");
+        let differences = HeaderDifference::collect(
+            &request,
+            &headers(&[("x-a", "1")]),
+            &headers(&[("x-b", "2")]),
+        );
+
+        let names = differences
+            .iter()
+            .map(|difference| (difference.name.as_str(), difference.left.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, [("x-a", true), ("x-b", false)]);
+    }
+}
@@ -0,0 +1,134 @@
+mod render;
+
+use std::fmt::Display;
+
+use clap::ValueEnum;
+use serde_json::Value;
+
+pub use render::format_response;
+
+/// How the differences between two bodies are shown.
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Renderer {
+    /// Built-in colored side by side view
+    #[default]
+    SideBySide,
+    /// Built-in colored unified view
+    Unified,
+    /// External `delta` process, requires `git-delta` to be installed
+    Delta,
+}
+
+pub enum Change {
+    Added(Value),
+    Removed(Value),
+    Changed { left: Value, right: Value },
+}
+
+/// A single structural difference between two JSON documents, located by its JSON path.
+pub struct Difference {
+    pub path: String,
+    pub change: Change,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.change {
+            Change::Added(value) => write!(f, "+ {}: {}", self.path, preview(value)),
+            Change::Removed(value) => write!(f, "- {}: {}", self.path, preview(value)),
+            Change::Changed { left, right } => {
+                write!(
+                    f,
+                    "~ {}: {} => {}",
+                    self.path,
+                    preview(left),
+                    preview(right)
+                )
+            }
+        }
+    }
+}
+
+/// Compares two JSON trees and returns every path whose value was added, removed or changed.
+/// Arrays are compared index by index.
+pub fn json_differences(left: &Value, right: &Value) -> Vec<Difference> {
+    let mut differences = vec![];
+    collect_differences("$", left, right, &mut differences);
+    differences
+}
+
+fn collect_differences(path: &str, left: &Value, right: &Value, out: &mut Vec<Difference>) {
+    match (left, right) {
+        (Value::Object(left), Value::Object(right)) => {
+            for (key, left_value) in left {
+                let child = object_path(path, key);
+                match right.get(key) {
+                    Some(right_value) => collect_differences(&child, left_value, right_value, out),
+                    None => out.push(Difference {
+                        path: child,
+                        change: Change::Removed(left_value.clone()),
+                    }),
+                }
+            }
+
+            for (key, right_value) in right {
+                if !left.contains_key(key) {
+                    out.push(Difference {
+                        path: object_path(path, key),
+                        change: Change::Added(right_value.clone()),
+                    });
+                }
+            }
+        }
+        (Value::Array(left), Value::Array(right)) => {
+            for index in 0..left.len().max(right.len()) {
+                let child = format!("{path}[{index}]");
+                match (left.get(index), right.get(index)) {
+                    (Some(left_value), Some(right_value)) => {
+                        collect_differences(&child, left_value, right_value, out);
+                    }
+                    (Some(left_value), None) => out.push(Difference {
+                        path: child,
+                        change: Change::Removed(left_value.clone()),
+                    }),
+                    (None, Some(right_value)) => out.push(Difference {
+                        path: child,
+                        change: Change::Added(right_value.clone()),
+                    }),
+                    (None, None) => unreachable!("Index is bounded by the longest array"),
+                }
+            }
+        }
+        (left, right) if left != right => out.push(Difference {
+            path: path.to_string(),
+            change: Change::Changed {
+                left: left.clone(),
+                right: right.clone(),
+            },
+        }),
+        _ => {}
+    }
+}
+
+fn object_path(parent: &str, key: &str) -> String {
+    let is_identifier = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    if is_identifier {
+        format!("{parent}.{key}")
+    } else {
+        format!("{parent}[{}]", Value::String(key.to_string()))
+    }
+}
+
+fn preview(value: &Value) -> String {
+    const MAX_PREVIEW: usize = 60;
+
+    let text = value.to_string();
+    if text.chars().count() > MAX_PREVIEW {
+        format!("{}...", text.chars().take(MAX_PREVIEW).collect::<String>())
+    } else {
+        text
+    }
+}
@@ -0,0 +1,219 @@
+use std::{fmt::Write as _, io::Write, ops::Range, process::Command};
+
+use anyhow::{Context, Result, anyhow};
+use console::{Style, measure_text_width, style};
+use serde_json::Value;
+use similar::{DiffTag, TextDiff};
+use tempfile::NamedTempFile;
+
+use super::{Change, Renderer, json_differences};
+use crate::client::Response;
+
+const CONTEXT_LINES: usize = 3;
+const LINE_NUMBER_WIDTH: usize = 5;
+const SEPARATOR: &str = " │ ";
+
+/// Renders the differences between two bodies. JSON bodies get a structural summary listing every
+/// changed path above the line based view.
+pub fn render(renderer: Renderer, left: &str, right: &str, width: usize) -> Result<String> {
+    if let Renderer::Delta = renderer {
+        return delta(left, right, width);
+    }
+
+    let mut out = String::new();
+    if let (Ok(left), Ok(right)) = (
+        serde_json::from_str::<Value>(left),
+        serde_json::from_str::<Value>(right),
+    ) {
+        for difference in json_differences(&left, &right) {
+            let line = match difference.change {
+                Change::Added(_) => style(difference.to_string()).green(),
+                Change::Removed(_) => style(difference.to_string()).red(),
+                Change::Changed { .. } => style(difference.to_string()).yellow(),
+            };
+            writeln!(out, "{line}")?;
+        }
+    }
+
+    match renderer {
+        Renderer::SideBySide => side_by_side(left, right, width, &mut out)?,
+        Renderer::Unified => unified(left, right, &mut out)?,
+        Renderer::Delta => unreachable!("Delta is rendered by the external process"),
+    }
+
+    Ok(out)
+}
+
+/// Formats the header of a compared pair followed by its status, header and body differences.
+pub fn format_response(response: &Response, renderer: Renderer, width: usize) -> Result<String> {
+    let separator = if response.is_equal() { "==" } else { "=>" };
+    let mut out = format!(
+        "{}: {} [{}] {separator} {} [{}]\n",
+        response.name,
+        response.left.url,
+        response.left.status_label(),
+        response.right.url,
+        response.right.status_label()
+    );
+
+    if response.left.status_code != response.right.status_code {
+        let line = format!(
+            "Status code {} => {}",
+            response.left.status_code, response.right.status_code
+        );
+        writeln!(out, "{}", style(line).red().bold())?;
+    }
+
+    for error in &response.status_errors {
+        writeln!(out, "{}", style(error).red().bold())?;
+    }
+
+    for difference in &response.header_differences {
+        writeln!(out, "{}", style(difference).yellow())?;
+    }
+
+    if let Some(error) = &response.latency_error {
+        writeln!(out, "{}", style(error).yellow())?;
+    }
+
+    if !response.is_body_equal() {
+        out.push_str(&render(
+            renderer,
+            &response.left.text,
+            &response.right.text,
+            width,
+        )?);
+    }
+
+    Ok(out)
+}
+
+fn unified(left: &str, right: &str, out: &mut String) -> Result<()> {
+    let diff = TextDiff::from_lines(left, right);
+    for group in diff.grouped_ops(CONTEXT_LINES) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+
+        let old = first.old_range().start..last.old_range().end;
+        let new = first.new_range().start..last.new_range().end;
+        writeln!(
+            out,
+            "{}",
+            style(format!(
+                "@@ -{},{} +{},{} @@",
+                old.start + 1,
+                old.len(),
+                new.start + 1,
+                new.len()
+            ))
+            .cyan()
+        )?;
+
+        for op in &group {
+            for change in diff.iter_changes(op) {
+                let line = change.value().trim_end_matches('\n');
+                match change.tag() {
+                    similar::ChangeTag::Delete => {
+                        writeln!(out, "{}", style(format!("-{line}")).red())?;
+                    }
+                    similar::ChangeTag::Insert => {
+                        writeln!(out, "{}", style(format!("+{line}")).green())?;
+                    }
+                    similar::ChangeTag::Equal => writeln!(out, " {line}")?,
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn side_by_side(left: &str, right: &str, width: usize, out: &mut String) -> Result<()> {
+    let diff = TextDiff::from_lines(left, right);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+    let column = width.saturating_sub(measure_text_width(SEPARATOR)) / 2;
+    let plain = Style::new();
+    let removed = Style::new().red();
+    let added = Style::new().green();
+
+    for (index, group) in diff.grouped_ops(CONTEXT_LINES).iter().enumerate() {
+        if index > 0 {
+            writeln!(out, "{}", style("┈".repeat(width)).dim())?;
+        }
+
+        for op in group {
+            let (old, new) = (op.old_range(), op.new_range());
+            let (left_style, right_style) = match op.tag() {
+                DiffTag::Equal => (&plain, &plain),
+                DiffTag::Delete | DiffTag::Insert | DiffTag::Replace => (&removed, &added),
+            };
+
+            for row in 0..old.len().max(new.len()) {
+                let left_cell = cell(old_lines, &old, row, column, left_style);
+                let right_cell = cell(new_lines, &new, row, column, right_style);
+                writeln!(out, "{left_cell}{}{right_cell}", style(SEPARATOR).dim())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn cell(lines: &[&str], range: &Range<usize>, row: usize, column: usize, style: &Style) -> String {
+    let index = range.start + row;
+    if index >= range.end {
+        return " ".repeat(column);
+    }
+
+    let text_width = column.saturating_sub(LINE_NUMBER_WIDTH);
+    let text = lines[index].trim_end_matches('\n');
+    let text = console::truncate_str(text, text_width, "");
+    let padding = text_width.saturating_sub(measure_text_width(&text));
+    let text = format!("{text}{}", " ".repeat(padding));
+
+    format!(
+        "{}{}",
+        console::style(format!("{:>4} ", index + 1)).dim(),
+        style.apply_to(text)
+    )
+}
+
+fn delta(left: &str, right: &str, width: usize) -> Result<String> {
+    let mut left_file = NamedTempFile::new().context("Failed to create temp file")?;
+    left_file
+        .write_all(left.as_bytes())
+        .context("Failed to write to temp file")?;
+
+    let mut right_file = NamedTempFile::new().context("Failed to create temp file")?;
+    right_file
+        .write_all(right.as_bytes())
+        .context("Failed to write to temp file")?;
+
+    let output = Command::new("delta")
+        .arg(left_file.path())
+        .arg(right_file.path())
+        .arg("--default-language=json")
+        .arg(format!("--width={width}"))
+        .arg("--file-style=omit")
+        .arg("-s")
+        .output()
+        .map_err(|e| anyhow!("Failed to run delta, make sure you have `git-delta` from `https://github.com/dandavison/delta` installed or choose a built-in renderer: {e}"))?;
+
+    String::from_utf8(output.stdout).context("Delta output is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pads_cells_to_the_display_width() {
+        let lines = ["ascii\n", "日本語のテキスト\n", "emoji 🎉🎉🎉🎉\n"];
+        for row in 0..lines.len() {
+            let cell = cell(&lines, &(0..lines.len()), row, 14, &Style::new());
+            assert_eq!(measure_text_width(&cell), 14, "{cell:?}");
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use anyhow::{Context, Result, anyhow};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use serde_json::Value;
+
+use crate::{
+    args::GenerateArgs,
+    client::{Config, PartDefaults, PartRequestConfig, RequestsConfig},
+};
+
+/// Characters encoded in path parameters, everything but the unreserved characters of URLs.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// Writes a config comparing every GET operation of an `OpenAPI` 3 document between two base
+/// URLs.
+pub fn generate(args: &GenerateArgs) -> Result<()> {
+    let text = fs::read_to_string(&args.openapi)
+        .with_context(|| format!("Failed to read {}", args.openapi.display()))?;
+    let spec: Value = serde_yaml::from_str(&text).with_context(|| {
+        format!(
+            "OpenAPI document {} is not valid YAML or JSON",
+            args.openapi.display()
+        )
+    })?;
+
+    let server = spec
+        .pointer("/servers/0/url")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let base_url = |base: &Option<String>, side: &str| {
+        base.clone().or_else(|| server.clone()).ok_or_else(|| {
+            anyhow!(
+                "The document has no servers, the {side} base URL must be given with --{side}-base"
+            )
+        })
+    };
+
+    let config = Config {
+        left: PartDefaults {
+            base_url: Some(base_url(&args.left_base, "left")?),
+            ..PartDefaults::default()
+        },
+        right: PartDefaults {
+            base_url: Some(base_url(&args.right_base, "right")?),
+            ..PartDefaults::default()
+        },
+        ..Config::from(requests(&spec, &path_order(&text)?)?)
+    };
+
+    write(&config, args.out.as_deref())
+}
+
+/// Writes a generated config to the path, or to stdout without one. Its `$` are escaped as `$$`,
+/// the values are written as they were found and mustn't be expanded when the config is loaded.
+pub fn write(config: &Config, out: Option<&Path>) -> Result<()> {
+    let toml = toml::to_string(config)
+        .context("Failed to serialize generated config")?
+        .replace('$', "$$");
+    if let Some(path) = out {
+        fs::write(path, toml)
+            .with_context(|| format!("Failed to write config to {}", path.display()))
+    } else {
+        print!("{toml}");
+        Ok(())
+    }
+}
+
+/// Paths of the document in the order they are written, which JSON objects don't keep.
+fn path_order(text: &str) -> Result<Vec<String>> {
+    let spec: serde_yaml::Value = serde_yaml::from_str(text)?;
+    Ok(spec
+        .get("paths")
+        .and_then(serde_yaml::Value::as_mapping)
+        .into_iter()
+        .flatten()
+        .filter_map(|(path, _)| path.as_str())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Requests for the GET operations, in the order of their paths in the document.
+fn requests(spec: &Value, order: &[String]) -> Result<Vec<RequestsConfig>> {
+    let paths = spec
+        .get("paths")
+        .and_then(Value::as_object)
+        .context("The OpenAPI document has no paths")?;
+
+    let mut requests = vec![];
+    for path in order {
+        let Some((item, operation)) = paths
+            .get(path)
+            .and_then(|item| item.get("get").map(|operation| (item, operation)))
+        else {
+            continue;
+        };
+
+        match request(spec, path, item, operation) {
+            Ok(request) => requests.push(request),
+            Err(e) => eprintln!("Skipping GET {path}: {e}"),
+        }
+    }
+
+    Ok(requests)
+}
+
+/// A request for a GET operation, its parameters filled with their examples. Optional parameters
+/// without an example are left out.
+fn request(spec: &Value, path: &str, item: &Value, operation: &Value) -> Result<RequestsConfig> {
+    // Parameters of the operation override the ones of its path with the same name and location.
+    let mut parameters: Vec<&Value> = vec![];
+    for parameter in [item, operation]
+        .into_iter()
+        .filter_map(|value| value.get("parameters").and_then(Value::as_array))
+        .flatten()
+    {
+        let parameter = resolve(spec, parameter)?;
+        parameters.retain(|existing| {
+            existing.get("name") != parameter.get("name")
+                || existing.get("in") != parameter.get("in")
+        });
+        parameters.push(parameter);
+    }
+
+    let mut part = PartRequestConfig {
+        url: path.to_string(),
+        ..PartRequestConfig::default()
+    };
+
+    for parameter in parameters {
+        let name = parameter
+            .get("name")
+            .and_then(Value::as_str)
+            .context("Parameter without a name")?;
+        let location = parameter
+            .get("in")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let required = location == "path"
+            || parameter
+                .get("required")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+
+        let Some(example) = example(spec, parameter)? else {
+            if required {
+                return Err(anyhow!(
+                    "no example for required {location} parameter {name}"
+                ));
+            }
+            continue;
+        };
+
+        match location {
+            "path" => {
+                let example = utf8_percent_encode(&example, PATH_SEGMENT).to_string();
+                part.url = part.url.replace(&format!("{{{name}}}"), &example);
+            }
+            "query" => {
+                part.query.insert(name.to_string(), example);
+            }
+            "header" => {
+                part.headers.insert(name.to_string(), example);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(start) = part.url.find('{') {
+        let end = part.url[start..]
+            .find('}')
+            .map_or(part.url.len(), |end| start + end + 1);
+        return Err(anyhow!(
+            "path parameter {} is not declared",
+            &part.url[start..end]
+        ));
+    }
+
+    let name = operation
+        .get("operationId")
+        .and_then(Value::as_str)
+        .map_or_else(|| format!("GET {path}"), str::to_string);
+    let tags = operation
+        .get("tags")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::to_string)
+        .collect();
+
+    Ok(RequestsConfig {
+        name,
+        tags,
+        left: part.clone(),
+        right: part,
+        ..RequestsConfig::default()
+    })
+}
+
+/// Example of a parameter, from the parameter itself, its first named example or its schema.
+fn example(spec: &Value, parameter: &Value) -> Result<Option<String>> {
+    let mut candidates = vec![parameter.get("example").cloned()];
+    if let Some(examples) = parameter.get("examples").and_then(Value::as_object) {
+        for example in examples.values() {
+            candidates.push(resolve(spec, example)?.get("value").cloned());
+        }
+    }
+
+    if let Some(schema) = parameter.get("schema") {
+        let schema = resolve(spec, schema)?;
+        candidates.push(schema.get("example").cloned());
+        candidates.push(schema.get("default").cloned());
+        candidates.push(schema.pointer("/enum/0").cloned());
+    }
+
+    Ok(candidates
+        .into_iter()
+        .flatten()
+        .next()
+        .map(|value| match value {
+            Value::String(value) => value,
+            value => value.to_string(),
+        }))
+}
+
+/// Follows a local `$ref` such as `#/components/parameters/limit`.
+fn resolve<'a>(spec: &'a Value, value: &'a Value) -> Result<&'a Value> {
+    let mut value = value;
+    let mut seen = HashSet::new();
+    while let Some(reference) = value.get("$ref").and_then(Value::as_str) {
+        if !seen.insert(reference) {
+            return Err(anyhow!("circular reference {reference}"));
+        }
+
+        value = reference
+            .strip_prefix('#')
+            .and_then(|pointer| spec.pointer(pointer))
+            .ok_or_else(|| anyhow!("unresolved reference {reference}"))?;
+    }
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::template;
+
+    #[test]
+    fn writes_values_that_load_back_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.toml");
+        let body = r#"{"home": "${HOME}", "price": "$5", "escaped": "$$"}"#;
+        let config = Config::from(vec![RequestsConfig {
+            name: "$name".to_string(),
+            left: PartRequestConfig {
+                url: "http://a/${id}".to_string(),
+                body: Some(body.to_string()),
+                ..PartRequestConfig::default()
+            },
+            ..RequestsConfig::default()
+        }]);
+
+        write(&config, Some(&path)).unwrap();
+        let toml = fs::read_to_string(&path).unwrap();
+        assert!(toml.starts_with("[[requests]]"), "{toml}");
+        let envs = HashMap::from([("HOME".to_string(), "/root".to_string())]);
+        let loaded: Config =
+            toml::from_str(&template::render(&path, &toml, &envs).unwrap()).unwrap();
+
+        let request = &loaded.requests[0];
+        assert_eq!(request.name, "$name");
+        assert_eq!(request.left.url, "http://a/${id}");
+        assert_eq!(request.left.body.as_deref(), Some(body));
+    }
+}
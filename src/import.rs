@@ -0,0 +1,687 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::{
+    args::ImportArgs,
+    client::{BasicAuth, Config, PartRequestConfig, RequestsConfig},
+    generate,
+};
+
+/// Headers set by the client itself or changing the encoding of the response, left out of
+/// imported requests.
+const SKIPPED_HEADERS: [&str; 4] = ["accept-encoding", "connection", "content-length", "host"];
+
+/// Options of curl without a value that don't change the request.
+const IGNORED_CURL_FLAGS: [&str; 10] = [
+    "--compressed",
+    "--silent",
+    "--show-error",
+    "--location",
+    "--insecure",
+    "--include",
+    "--verbose",
+    "--fail",
+    "--globoff",
+    "--no-buffer",
+];
+
+/// Short options of curl without a value that don't change the request, they can be combined
+/// such as `-sSL`.
+const IGNORED_CURL_SHORT_FLAGS: &str = "sSLkivfgN";
+
+/// Characters curl encodes in the content of `--data-urlencode`, all but the unreserved ones.
+const DATA_URLENCODED: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// A `--map-host FROM=TO` mapping, `FROM` being a host or a host and port.
+struct HostMapping<'a> {
+    from: &'a str,
+
+    /// Origin replacing the one of a matching URL, its scheme only applies when `TO` had one.
+    to: Url,
+    has_scheme: bool,
+}
+
+#[derive(Deserialize)]
+struct Har {
+    log: HarLog,
+}
+
+#[derive(Deserialize)]
+struct HarLog {
+    entries: Vec<HarEntry>,
+}
+
+#[derive(Deserialize)]
+struct HarEntry {
+    request: HarRequest,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarRequest {
+    method: String,
+    url: String,
+
+    #[serde(default)]
+    headers: Vec<HarPair>,
+
+    #[serde(default)]
+    post_data: Option<HarPostData>,
+}
+
+#[derive(Deserialize)]
+struct HarPair {
+    name: String,
+    value: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct HarPostData {
+    #[serde(default)]
+    mime_type: String,
+
+    #[serde(default)]
+    text: Option<String>,
+
+    #[serde(default)]
+    params: Vec<HarPair>,
+}
+
+/// Writes a config with a request for every entry of a HAR export or every curl command.
+pub fn import(args: &ImportArgs) -> Result<()> {
+    let parts = if let Some(path) = &args.har {
+        let har = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        from_har(&har).with_context(|| format!("Invalid HAR file {}", path.display()))?
+    } else if let Some(path) = &args.curl {
+        let commands = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        from_curl(&commands)
+    } else {
+        bail!("Either --har or --curl must be given");
+    };
+
+    let mappings = args
+        .map_host
+        .iter()
+        .map(|mapping| host_mapping(mapping))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut names = HashMap::<String, usize>::new();
+    let requests = parts
+        .into_iter()
+        .map(|left| {
+            let name = format!(
+                "{} {}",
+                left.method.as_deref().unwrap_or("GET"),
+                Url::parse(&left.url)
+                    .map_or_else(|_| left.url.clone(), |url| url.path().to_string())
+            );
+            let count = names.entry(name.clone()).or_default();
+            *count += 1;
+
+            let mut right = left.clone();
+            right.url = map_host(&left.url, &mappings);
+            RequestsConfig {
+                name: if *count > 1 {
+                    format!("{name} ({count})")
+                } else {
+                    name
+                },
+                left,
+                right,
+                ..RequestsConfig::default()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    generate::write(&Config::from(requests), args.out.as_deref())
+}
+
+fn from_har(har: &str) -> Result<Vec<PartRequestConfig>> {
+    let har: Har = serde_json::from_str(har)?;
+    Ok(har
+        .log
+        .entries
+        .into_iter()
+        .map(|entry| {
+            let request = entry.request;
+            let mut part = PartRequestConfig {
+                url: request.url,
+                method: Some(request.method.to_uppercase()).filter(|method| method != "GET"),
+                ..PartRequestConfig::default()
+            };
+
+            for header in request.headers {
+                insert_header(&mut part, &header.name, header.value);
+            }
+
+            if let Some(post_data) = request.post_data {
+                if !post_data.mime_type.is_empty()
+                    && !part
+                        .headers
+                        .keys()
+                        .any(|name| name.eq_ignore_ascii_case("content-type"))
+                {
+                    part.headers
+                        .insert("Content-Type".to_string(), post_data.mime_type);
+                }
+
+                match post_data.text.filter(|text| !text.is_empty()) {
+                    Some(text) => part.body = Some(text),
+                    None => {
+                        part.form = post_data
+                            .params
+                            .into_iter()
+                            .map(|param| (param.name, param.value))
+                            .collect();
+                    }
+                }
+            }
+
+            split_query(&mut part);
+            part
+        })
+        .collect())
+}
+
+/// Parses every curl command of the text, skipping the ones that can't be imported.
+fn from_curl(commands: &str) -> Vec<PartRequestConfig> {
+    let mut parts = vec![];
+    let mut command = String::new();
+    let mut first_line = 1;
+    for (index, line) in commands.lines().enumerate() {
+        if command.is_empty() {
+            first_line = index + 1;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+        }
+
+        if let Some(continued) = line.trim_end().strip_suffix('\\') {
+            command.push_str(continued);
+            command.push(' ');
+            continue;
+        }
+
+        command.push_str(line);
+        match tokenize(&command).and_then(from_curl_command) {
+            Ok(part) => parts.push(part),
+            Err(e) => eprintln!("Skipping curl command on line {first_line}: {e}"),
+        }
+        command.clear();
+    }
+
+    parts
+}
+
+fn from_curl_command(tokens: Vec<String>) -> Result<PartRequestConfig> {
+    let mut tokens = tokens.into_iter();
+    if tokens.next().as_deref() != Some("curl") {
+        bail!("not a curl command");
+    }
+
+    let mut part = PartRequestConfig::default();
+    let mut method = None;
+    let mut data = vec![];
+    let mut json = false;
+    let mut get = false;
+    while let Some(token) = tokens.next() {
+        // Values of short options can be attached to them, such as `-XPOST`.
+        let (option, mut attached) = match token.strip_prefix('-') {
+            Some(rest)
+                if !rest.starts_with('-')
+                    && rest.len() > 1
+                    && rest.starts_with(['X', 'H', 'd', 'u', 'b', 'A', 'e', 'm']) =>
+            {
+                (token[..2].to_string(), Some(token[2..].to_string()))
+            }
+            _ => (token, None),
+        };
+
+        let mut value = || {
+            attached
+                .take()
+                .or_else(|| tokens.next())
+                .ok_or_else(|| anyhow!("{option} is missing its value"))
+        };
+
+        match option.as_str() {
+            "-X" | "--request" => method = Some(value()?.to_uppercase()),
+            "-H" | "--header" => {
+                let header = value()?;
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("invalid header {header}"))?;
+                insert_header(&mut part, name.trim(), value.trim().to_string());
+            }
+            "-d" | "--data" | "--data-raw" | "--data-binary" | "--data-ascii" => {
+                data.push(value()?);
+            }
+            "--data-urlencode" => data.push(url_encode_data(&value()?)?),
+            "--json" => {
+                data.push(value()?);
+                json = true;
+            }
+            "-u" | "--user" => {
+                let user = value()?;
+                part.basic_auth = Some(match user.split_once(':') {
+                    Some((username, password)) => BasicAuth {
+                        username: username.to_string(),
+                        password: Some(password.to_string()),
+                    },
+                    None => BasicAuth {
+                        username: user,
+                        password: None,
+                    },
+                });
+            }
+            "-b" | "--cookie" => insert_header(&mut part, "Cookie", value()?),
+            "-A" | "--user-agent" => insert_header(&mut part, "User-Agent", value()?),
+            "-e" | "--referer" => insert_header(&mut part, "Referer", value()?),
+            "-m" | "--max-time" => {
+                let timeout = value()?;
+                part.timeout = Some(
+                    timeout
+                        .parse()
+                        .with_context(|| format!("invalid timeout {timeout}"))?,
+                );
+            }
+            "--url" => part.url = value()?,
+            "-G" | "--get" => get = true,
+            "-I" | "--head" => method = Some("HEAD".to_string()),
+            flag if IGNORED_CURL_FLAGS.contains(&flag) => {}
+            flag if flag.len() > 1
+                && flag.starts_with('-')
+                && flag[1..]
+                    .chars()
+                    .all(|c| IGNORED_CURL_SHORT_FLAGS.contains(c)) => {}
+            flag if flag.starts_with('-') => bail!("unsupported option {flag}"),
+            url => part.url = url.to_string(),
+        }
+    }
+
+    if part.url.is_empty() {
+        bail!("no URL");
+    }
+
+    apply_data(&mut part, &data, json, get)?;
+    part.method = method
+        .or_else(|| (!data.is_empty() && !get).then(|| "POST".to_string()))
+        .filter(|method| method != "GET");
+    split_query(&mut part);
+    Ok(part)
+}
+
+/// Sends the `--data` of a curl command as the body, or in the query with `--get`.
+fn apply_data(part: &mut PartRequestConfig, data: &[String], json: bool, get: bool) -> Result<()> {
+    if data.iter().any(|data| data.starts_with('@')) {
+        bail!("reading the body from a file is not supported");
+    }
+
+    if data.is_empty() {
+        return Ok(());
+    }
+
+    if get {
+        let separator = if part.url.contains('?') { '&' } else { '?' };
+        part.url = format!("{}{separator}{}", part.url, data.join("&"));
+        return Ok(());
+    }
+
+    let content_type = if json {
+        "application/json"
+    } else {
+        "application/x-www-form-urlencoded"
+    };
+    if !part
+        .headers
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case("content-type"))
+    {
+        part.headers
+            .insert("Content-Type".to_string(), content_type.to_string());
+    }
+
+    part.body = Some(data.join("&"));
+    Ok(())
+}
+
+/// Encodes the content of a `--data-urlencode` value the way curl does, its `name=` prefix is kept
+/// as is and a leading `=` is dropped.
+fn url_encode_data(data: &str) -> Result<String> {
+    let (name, content) = match data.find(['=', '@']) {
+        Some(index) if data[index..].starts_with('@') => {
+            bail!("reading --data-urlencode content from a file is not supported")
+        }
+        Some(0) => (None, &data[1..]),
+        Some(index) => (Some(&data[..index]), &data[index + 1..]),
+        None => (None, data),
+    };
+
+    let content = utf8_percent_encode(content, DATA_URLENCODED);
+    Ok(match name {
+        Some(name) => format!("{name}={content}"),
+        None => content.to_string(),
+    })
+}
+
+/// Splits a command line into words, following the quoting rules of a POSIX shell and the `$'...'`
+/// quotes of bash.
+fn tokenize(command: &str) -> Result<Vec<String>> {
+    let mut tokens = vec![];
+    let mut token: Option<String> = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => tokens.extend(token.take()),
+            '\'' => {
+                let quoted: String = chars.by_ref().take_while(|&c| c != '\'').collect();
+                token.get_or_insert_default().push_str(&quoted);
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                let token = token.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => token.push('\n'),
+                            Some('t') => token.push('\t'),
+                            Some('r') => token.push('\r'),
+                            Some(c) => token.push(c),
+                            None => bail!("unterminated quote"),
+                        },
+                        Some(c) => token.push(c),
+                        None => bail!("unterminated quote"),
+                    }
+                }
+            }
+            '"' => {
+                let token = token.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => token.push(c),
+                            Some(c) => {
+                                token.push('\\');
+                                token.push(c);
+                            }
+                            None => bail!("unterminated quote"),
+                        },
+                        Some(c) => token.push(c),
+                        None => bail!("unterminated quote"),
+                    }
+                }
+            }
+            '\\' => {
+                if let Some(c) = chars.next() {
+                    token.get_or_insert_default().push(c);
+                }
+            }
+            c => token.get_or_insert_default().push(c),
+        }
+    }
+
+    tokens.extend(token);
+    Ok(tokens)
+}
+
+fn insert_header(part: &mut PartRequestConfig, name: &str, value: String) {
+    if name.starts_with(':') || SKIPPED_HEADERS.contains(&name.to_lowercase().as_str()) {
+        return;
+    }
+
+    part.headers.insert(name.to_string(), value);
+}
+
+/// Moves the query of the URL to the `query` table of the request. A query repeating a parameter,
+/// such as `?id=1&id=2`, stays in the URL as the table only holds one value per name.
+fn split_query(part: &mut PartRequestConfig) {
+    let Ok(mut url) = Url::parse(&part.url) else {
+        return;
+    };
+
+    if url.query().is_none() {
+        return;
+    }
+
+    let pairs = url.query_pairs().into_owned().collect::<Vec<_>>();
+    let mut names = HashSet::new();
+    if !pairs
+        .iter()
+        .all(|(name, _)| names.insert(name) && !part.query.contains_key(name))
+    {
+        return;
+    }
+
+    part.query.extend(pairs);
+    url.set_query(None);
+    part.url = url.to_string();
+}
+
+fn host_mapping(mapping: &str) -> Result<HostMapping<'_>> {
+    let (from, to) = mapping
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Invalid host mapping {mapping}, expecting FROM=TO"))?;
+    let has_scheme = to.contains("://");
+    let to = if has_scheme {
+        Url::parse(to)
+    } else {
+        Url::parse(&format!("http://{to}"))
+    }
+    .ok()
+    .filter(|to| to.has_host() && ["http", "https"].contains(&to.scheme()))
+    .ok_or_else(|| {
+        anyhow!("Invalid host mapping {mapping}, {to} is not a host, host and port or origin")
+    })?;
+
+    Ok(HostMapping {
+        from,
+        to,
+        has_scheme,
+    })
+}
+
+/// Replaces the origin of the URL by the one of the first mapping whose `FROM` is either its host
+/// or its host and port, such as `[::1]:8080`.
+fn map_host(url: &str, mappings: &[HostMapping]) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    let Some(host) = parsed.host_str() else {
+        return url.to_string();
+    };
+
+    let host_and_port = parsed
+        .port_or_known_default()
+        .map(|port| format!("{host}:{port}"));
+    let Some(mapping) = mappings
+        .iter()
+        .find(|mapping| mapping.from == host || Some(mapping.from) == host_and_port.as_deref())
+    else {
+        return url.to_string();
+    };
+
+    if (mapping.has_scheme && parsed.set_scheme(mapping.to.scheme()).is_err())
+        || parsed.set_host(mapping.to.host_str()).is_err()
+        || parsed.set_port(mapping.to.port()).is_err()
+    {
+        return url.to_string();
+    }
+
+    parsed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(command: &str) -> Vec<String> {
+        tokenize(command).unwrap()
+    }
+
+    #[test]
+    fn splits_words_on_whitespace() {
+        assert_eq!(
+            tokens("curl  -X POST\thttp://a/b "),
+            ["curl", "-X", "POST", "http://a/b"]
+        );
+    }
+
+    #[test]
+    fn keeps_single_quotes_literal() {
+        assert_eq!(
+            tokens(r#"curl -H 'X-A: "b" \n $c' http://a"#),
+            ["curl", "-H", r#"X-A: "b" \n $c"#, "http://a"]
+        );
+    }
+
+    #[test]
+    fn unescapes_double_quotes() {
+        assert_eq!(
+            tokens(r#"curl -d "{\"a\": \"\$b\\\"} \n" http://a"#),
+            ["curl", "-d", r#"{"a": "$b\"} \n"#, "http://a"]
+        );
+    }
+
+    #[test]
+    fn unescapes_ansi_c_quotes() {
+        assert_eq!(
+            tokens(r"curl --data-raw $'a\nb\'c' http://a"),
+            ["curl", "--data-raw", "a\nb'c", "http://a"]
+        );
+    }
+
+    #[test]
+    fn joins_adjacent_quoted_parts() {
+        assert_eq!(tokens(r#"curl a'b'"c"\ d"#), ["curl", "abc d"]);
+        assert_eq!(tokens("curl ''"), ["curl", ""]);
+    }
+
+    #[test]
+    fn fails_on_unterminated_quotes() {
+        assert!(tokenize(r#"curl -d "abc"#).is_err());
+        assert!(tokenize(r"curl -d $'abc").is_err());
+    }
+
+    #[test]
+    fn parses_curl_commands() {
+        let part = from_curl_command(tokens(
+            "curl -XPUT 'http://a/users?page=2' -H 'Accept: application/json' \
+             -H 'Accept-Encoding: gzip' -u me:secret --compressed -sS -d '{\"a\":1}'",
+        ))
+        .unwrap();
+
+        assert_eq!(part.url, "http://a/users");
+        assert_eq!(part.method.as_deref(), Some("PUT"));
+        assert_eq!(part.query["page"], "2");
+        assert_eq!(part.headers["Accept"], "application/json");
+        assert!(!part.headers.contains_key("Accept-Encoding"));
+        assert_eq!(part.body.as_deref(), Some("{\"a\":1}"));
+
+        let basic_auth = part.basic_auth.unwrap();
+        assert_eq!(basic_auth.username, "me");
+        assert_eq!(basic_auth.password.as_deref(), Some("secret"));
+    }
+
+    #[test]
+    fn rejects_unsupported_options() {
+        let Err(error) = from_curl_command(tokens("curl --proxy http://p http://a")) else {
+            panic!("--proxy changes the request and can't be ignored");
+        };
+        assert_eq!(error.to_string(), "unsupported option --proxy");
+    }
+
+    #[test]
+    fn keeps_repeated_query_parameters_in_the_url() {
+        let part = from_curl_command(tokens("curl 'http://a/items?id=1&id=2&page=3'")).unwrap();
+        assert_eq!(part.url, "http://a/items?id=1&id=2&page=3");
+        assert!(part.query.is_empty());
+    }
+
+    #[test]
+    fn url_encodes_data_like_curl() {
+        assert_eq!(url_encode_data("a b&c").unwrap(), "a%20b%26c");
+        assert_eq!(url_encode_data("=x=y").unwrap(), "x%3Dy");
+        assert_eq!(url_encode_data("q=é/ü~").unwrap(), "q=%C3%A9%2F%C3%BC~");
+        assert_eq!(url_encode_data("q=a@b").unwrap(), "q=a%40b");
+        assert!(url_encode_data("@body.txt").is_err());
+        assert!(url_encode_data("q@body.txt").is_err());
+    }
+
+    #[test]
+    fn sends_url_encoded_data_in_the_query_with_get() {
+        let part = from_curl_command(tokens(
+            "curl -G http://a/search --data-urlencode 'q=a b&c' --data-urlencode 'n=1'",
+        ))
+        .unwrap();
+
+        assert_eq!(part.method, None);
+        assert_eq!(part.query["q"], "a b&c");
+        assert_eq!(part.query["n"], "1");
+    }
+
+    fn mapped(url: &str, mappings: &[&str]) -> String {
+        let mappings = mappings
+            .iter()
+            .map(|mapping| host_mapping(mapping).unwrap())
+            .collect::<Vec<_>>();
+        map_host(url, &mappings)
+    }
+
+    #[test]
+    fn maps_hosts() {
+        assert_eq!(
+            mapped("http://staging/a?b=1", &["staging=prod:8443"]),
+            "http://prod:8443/a?b=1"
+        );
+        assert_eq!(
+            mapped("http://staging:8080/a", &["staging:8080=prod"]),
+            "http://prod/a"
+        );
+        assert_eq!(
+            mapped(
+                "http://staging/a",
+                &["staging:80=https://prod.example.com/"]
+            ),
+            "https://prod.example.com/a"
+        );
+        assert_eq!(
+            mapped("http://other/a", &["staging=prod"]),
+            "http://other/a"
+        );
+    }
+
+    #[test]
+    fn maps_ipv6_hosts() {
+        assert_eq!(
+            mapped("http://[::1]:8080/a", &["[::1]:8080=[::2]:9090"]),
+            "http://[::2]:9090/a"
+        );
+        assert_eq!(
+            mapped("http://[::1]/a", &["[::1]=localhost:3000"]),
+            "http://localhost:3000/a"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_host_mappings() {
+        assert!(host_mapping("staging").is_err());
+        assert!(host_mapping("staging=a b").is_err());
+        assert!(host_mapping("staging=ftp://prod").is_err());
+    }
+}
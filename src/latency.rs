@@ -0,0 +1,107 @@
+use std::{fmt::Display, time::Duration};
+
+use crate::client::Response;
+
+const PERCENTILES: [f64; 3] = [50.0, 90.0, 99.0];
+
+/// Latency percentiles of each request collected over repeated runs.
+pub struct LatencySummary {
+    runs: usize,
+    rows: Vec<Row>,
+}
+
+struct Row {
+    name: String,
+    left: Vec<Duration>,
+    right: Vec<Duration>,
+}
+
+impl LatencySummary {
+    pub fn new(runs: &[Vec<Response>]) -> Self {
+        let mut rows: Vec<Row> = vec![];
+        for response in runs.iter().flatten() {
+            let index = if let Some(index) = rows.iter().position(|row| row.name == response.name) {
+                index
+            } else {
+                rows.push(Row {
+                    name: response.name.clone(),
+                    left: vec![],
+                    right: vec![],
+                });
+                rows.len() - 1
+            };
+
+            // Responses served from the cache or a snapshot weren't timed in this run.
+            if !response.left.cached {
+                rows[index].left.push(response.left.elapsed);
+            }
+            if !response.right.cached {
+                rows[index].right.push(response.right.elapsed);
+            }
+        }
+
+        for row in &mut rows {
+            row.left.sort();
+            row.right.sort();
+        }
+
+        Self {
+            runs: runs.len(),
+            rows,
+        }
+    }
+}
+
+impl Display for LatencySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name_width = self
+            .rows
+            .iter()
+            .map(|row| row.name.chars().count())
+            .max()
+            .unwrap_or_default()
+            .max("Request".len());
+
+        writeln!(f, "Latency over {} runs (p50 / p90 / p99)", self.runs)?;
+        writeln!(f, "{:<name_width$}  {:<24}  Right", "Request", "Left")?;
+
+        for row in &self.rows {
+            writeln!(
+                f,
+                "{:<name_width$}  {:<24}  {}",
+                row.name,
+                percentiles(&row.left),
+                percentiles(&row.right)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn percentiles(sorted: &[Duration]) -> String {
+    if sorted.is_empty() {
+        return "cached".to_string();
+    }
+
+    PERCENTILES
+        .iter()
+        .map(|&percentile| format!("{}ms", percentile_of(sorted, percentile).as_millis()))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// Nearest rank percentile of an already sorted list.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn percentile_of(sorted: &[Duration], percentile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
@@ -0,0 +1,388 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, mem,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use glob::Pattern;
+
+use crate::{
+    client::{Auth, BearerToken, Config, RequestsConfig},
+    template,
+};
+
+/// Loads the configs and the configs they include into a single one. Every request is resolved
+/// against the config it comes from: relative files are found next to that config and it
+/// inherits the defaults, ignore rules, timeouts and retries of that config and of the configs
+/// including it. A config included several times is only loaded the first time, with the settings
+/// of the config including it then. Request names must be unique across all of them.
+pub fn load(paths: &[PathBuf], envs: &HashMap<String, String>) -> Result<Config> {
+    let mut loaded = Config::default();
+    let mut seen = HashSet::new();
+    for path in paths {
+        load_file(
+            path,
+            &Config::default(),
+            envs,
+            &mut vec![],
+            &mut seen,
+            &mut loaded,
+        )?;
+    }
+
+    // Responses are matched back to their request by name.
+    let mut names = HashSet::new();
+    for request in &loaded.requests {
+        if !names.insert(request.name.as_str()) {
+            bail!(
+                "Request name {} is used more than once, `curpare check` shows where",
+                request.name
+            );
+        }
+    }
+
+    Ok(loaded)
+}
+
+/// Files matched by an include pattern of a config in `dir`, sorted by path.
+pub fn include_paths(dir: &Path, pattern: &str) -> Result<Vec<PathBuf>> {
+    let full_pattern = Path::new(&Pattern::escape(&dir.to_string_lossy())).join(pattern);
+    let mut paths = glob::glob(&full_pattern.to_string_lossy())
+        .with_context(|| format!("Invalid include pattern {pattern}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to read the files of include pattern {pattern}"))?;
+
+    if paths.is_empty() {
+        bail!("Include pattern {pattern} matches no file");
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+/// Fills in the settings a config doesn't set itself from the config including it. Ignore rules
+/// add up, variables and defaults set by the config itself take precedence.
+pub fn inherit(config: &mut Config, parent: &Config) {
+    for (name, value) in &parent.vars {
+        config
+            .vars
+            .entry(name.clone())
+            .or_insert_with(|| value.clone());
+    }
+
+    config
+        .ignore_lines
+        .splice(0..0, parent.ignore_lines.iter().cloned());
+    config
+        .ignore_paths
+        .splice(0..0, parent.ignore_paths.iter().cloned());
+    config.timeout = config.timeout.or(parent.timeout);
+    config.retries = config.retries.or(parent.retries);
+    config.retry_on = config.retry_on.take().or_else(|| parent.retry_on.clone());
+    config.left.inherit(&parent.left);
+    config.right.inherit(&parent.right);
+}
+
+/// Variables available to a config, the ones of the configs including it come before the
+/// environment.
+pub fn variables(parent: &Config, envs: &HashMap<String, String>) -> HashMap<String, String> {
+    envs.iter()
+        .chain(&parent.vars)
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+fn load_file(
+    path: &Path,
+    parent: &Config,
+    envs: &HashMap<String, String>,
+    including: &mut Vec<PathBuf>,
+    seen: &mut HashSet<PathBuf>,
+    loaded: &mut Config,
+) -> Result<()> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    if including.contains(&canonical) {
+        bail!("{} includes itself", path.display());
+    }
+    if !seen.insert(canonical.clone()) {
+        return Ok(());
+    }
+
+    let toml =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let toml = template::render(path, &toml, &variables(parent, envs))?;
+    let mut config: Config = toml::from_str(&toml)
+        .with_context(|| format!("Toml in path {} is not formatted correctly", path.display()))?;
+
+    config
+        .rate_interval()
+        .with_context(|| format!("Invalid config {}", path.display()))?;
+
+    let config_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for defaults in [&mut config.left, &mut config.right] {
+        resolve_token_file(&mut defaults.auth, config_dir);
+    }
+    inherit(&mut config, parent);
+
+    for mut request in mem::take(&mut config.requests) {
+        resolve(&mut request, &config, config_dir)?;
+        loaded.requests.push(request);
+    }
+
+    // The strictest rate limit of the configs applies to all of their requests.
+    loaded.rate_limit = match (loaded.rate_limit, config.rate_limit) {
+        (Some(loaded), Some(rate_limit)) => Some(loaded.min(rate_limit)),
+        (loaded, rate_limit) => loaded.or(rate_limit),
+    };
+    loaded.sequential |= config.sequential;
+
+    including.push(canonical);
+    for pattern in &config.include {
+        let paths = include_paths(config_dir, pattern)
+            .with_context(|| format!("Failed to include configs in {}", path.display()))?;
+        for include in paths {
+            load_file(&include, &config, envs, including, seen, loaded)?;
+        }
+    }
+    including.pop();
+
+    Ok(())
+}
+
+/// Resolves a request of a config in `config_dir` with the settings of that config.
+fn resolve(request: &mut RequestsConfig, config: &Config, config_dir: &Path) -> Result<()> {
+    let path = request.path.take();
+    for (side, part, defaults) in [
+        ("left", &mut request.left, &config.left),
+        ("right", &mut request.right, &config.right),
+    ] {
+        if let Some(body_file) = &part.body_file {
+            part.body_file = Some(resolve_file(config_dir, body_file));
+        }
+        resolve_token_file(&mut part.auth, config_dir);
+
+        part.inherit(defaults, path.as_deref())
+            .with_context(|| format!("Invalid {side} side of request {}", request.name))?;
+
+        part.timeout = part.timeout.or(config.timeout);
+        part.retries = part.retries.or(config.retries);
+        part.retry_on = part.retry_on.take().or_else(|| config.retry_on.clone());
+        part.check_ranges()
+            .with_context(|| format!("Invalid {side} side of request {}", request.name))?;
+    }
+
+    // Ignore rules add up, each side applies the rules of its configs, of its request and its
+    // own.
+    let ignore_lines = config
+        .ignore_lines
+        .iter()
+        .chain(&request.ignore_lines)
+        .cloned()
+        .collect::<Vec<_>>();
+    let ignore_paths = config
+        .ignore_paths
+        .iter()
+        .chain(&request.ignore_paths)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for part in [&mut request.left, &mut request.right] {
+        part.ignore_lines.extend(ignore_lines.iter().cloned());
+        part.ignore_paths.extend(ignore_paths.iter().cloned());
+    }
+
+    Ok(())
+}
+
+fn resolve_token_file(auth: &mut Option<Auth>, config_dir: &Path) {
+    if let Some(Auth::Bearer(BearerToken {
+        token_file: Some(token_file),
+        ..
+    })) = auth
+    {
+        *token_file = resolve_file(config_dir, token_file);
+    }
+}
+
+/// Absolute path of a file referenced by a config in `config_dir`, so it is still found from a
+/// config written with `--out` elsewhere.
+fn resolve_file(config_dir: &Path, file: &Path) -> PathBuf {
+    let file = config_dir.join(file);
+    std::path::absolute(&file).unwrap_or(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, toml: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, toml).unwrap();
+        path
+    }
+
+    fn request(name: &str) -> String {
+        format!(
+            "[[requests]]\nname = \"{name}\"\n[requests.left]\nurl = \"http://a/{name}\"\n\
+             [requests.right]\nurl = \"http://b/{name}\"\n"
+        )
+    }
+
+    fn names(config: &Config) -> Vec<&str> {
+        config
+            .requests
+            .iter()
+            .map(|request| request.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn sorts_included_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.toml", "a.toml", "c.txt"] {
+            write(dir.path(), name, "");
+        }
+
+        let paths = include_paths(dir.path(), "*.toml").unwrap();
+        assert_eq!(
+            paths,
+            [dir.path().join("a.toml"), dir.path().join("b.toml")]
+        );
+    }
+
+    #[test]
+    fn escapes_the_directory_of_the_including_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("[v1]");
+        write(&nested, "users.toml", "");
+
+        let paths = include_paths(&nested, "*.toml").unwrap();
+        assert_eq!(paths, [nested.join("users.toml")]);
+    }
+
+    #[test]
+    fn fails_on_patterns_matching_no_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = include_paths(dir.path(), "missing/*.toml").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Include pattern missing/*.toml matches no file"
+        );
+    }
+
+    #[test]
+    fn loads_included_requests_after_the_including_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = write(
+            dir.path(),
+            "root.toml",
+            &format!("include = [\"users/*.toml\"]\n{}", request("root")),
+        );
+        write(dir.path(), "users/b.toml", &request("b"));
+        write(dir.path(), "users/a.toml", &request("a"));
+
+        let config = load(&[root], &HashMap::new()).unwrap();
+        assert_eq!(names(&config), ["root", "a", "b"]);
+    }
+
+    #[test]
+    fn fails_on_include_cycles() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = write(dir.path(), "a.toml", "include = [\"b.toml\"]\n");
+        write(dir.path(), "b.toml", "include = [\"./a.toml\"]\n");
+
+        let Err(error) = load(&[a], &HashMap::new()) else {
+            panic!("A config including itself can't be loaded");
+        };
+        assert!(
+            format!("{error:#}").ends_with("./a.toml includes itself"),
+            "{error:#}"
+        );
+    }
+
+    #[test]
+    fn loads_configs_included_several_times_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = write(
+            dir.path(),
+            "root.toml",
+            "include = [\"a.toml\", \"b.toml\"]\n",
+        );
+        for name in ["a", "b"] {
+            write(
+                dir.path(),
+                &format!("{name}.toml"),
+                &format!("include = [\"common.toml\"]\n{}", request(name)),
+            );
+        }
+        write(dir.path(), "common.toml", &request("common"));
+
+        let config = load(&[root.clone(), root], &HashMap::new()).unwrap();
+        assert_eq!(names(&config), ["a", "common", "b"]);
+    }
+
+    #[test]
+    fn inherits_the_settings_of_including_configs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = write(
+            dir.path(),
+            "root.toml",
+            "include = [\"users.toml\"]\ntimeout = 5\nignore_lines = [\"root\"]\n\
+             [vars]\nHOST = \"http://root\"\n[left]\nbase_url = \"${HOST}\"\n",
+        );
+        write(
+            dir.path(),
+            "users.toml",
+            "ignore_lines = [\"users\"]\n[right]\nbase_url = \"${HOST}\"\n\
+             [[requests]]\nname = \"users\"\npath = \"/users\"\n\
+             [requests.left]\nbody_file = \"body.json\"\n[requests.right]\ntimeout = 1\n",
+        );
+
+        let config = load(&[root], &HashMap::new()).unwrap();
+        let request = &config.requests[0];
+        assert_eq!(request.left.url, "http://root/users");
+        assert_eq!(request.right.url, "http://root/users");
+        assert_eq!(request.left.timeout, Some(5.0));
+        assert_eq!(request.right.timeout, Some(1.0));
+        assert_eq!(request.left.ignore_lines, ["root", "users"]);
+        assert_eq!(
+            request.left.body_file.as_deref(),
+            Some(
+                std::path::absolute(dir.path().join("body.json"))
+                    .unwrap()
+                    .as_path()
+            )
+        );
+    }
+
+    #[test]
+    fn fails_on_duplicate_request_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = write(dir.path(), "a.toml", &request("dup"));
+        let b = write(dir.path(), "b.toml", &request("dup"));
+
+        let Err(error) = load(&[a, b], &HashMap::new()) else {
+            panic!("Responses of requests with the same name can't be told apart");
+        };
+        assert_eq!(
+            error.to_string(),
+            "Request name dup is used more than once, `curpare check` shows where"
+        );
+    }
+
+    #[test]
+    fn keeps_the_lowest_rate_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = write(dir.path(), "a.toml", "rate_limit = 5\n");
+        let b = write(dir.path(), "b.toml", "rate_limit = 2\nsequential = true\n");
+        let c = write(dir.path(), "c.toml", "");
+
+        let config = load(&[a, b, c], &HashMap::new()).unwrap();
+        assert_eq!(config.rate_limit, Some(2.0));
+        assert!(config.sequential);
+    }
+}
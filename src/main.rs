@@ -1,128 +1,306 @@
 #![warn(clippy::all, clippy::pedantic)]
 
+mod accept;
 mod args;
 mod client;
+mod diff;
+mod generate;
+mod import;
+mod latency;
+mod load;
+mod report;
+mod template;
+mod tui;
+mod validate;
 
 use std::{
-    fs::{File, remove_file},
-    io::Write,
+    fs::remove_file,
     path::{Path, PathBuf},
-    process::Command,
+    process::ExitCode,
 };
 
 use anyhow::{Context, Result, anyhow};
-use args::Args;
+use args::{Args, Command};
 use bat::PrettyPrinter;
 use clap::Parser;
-use client::{Client, Config, Response};
+use client::{Client, Config, RequestsConfig, Response};
+use console::style;
+use diff::Renderer;
 use indicatif::{ProgressBar, ProgressStyle};
-use tempfile::NamedTempFile;
+use latency::LatencySummary;
+use report::ReportFormat;
+
+/// Exit code of `--check` when at least one pair of responses differ.
+const EXIT_DIFFERENCES: u8 = 1;
+/// Exit code of `--check` when at least one request failed, takes precedence over differences.
+/// Also used when the run fails altogether, so failures are never mistaken for differences.
+const EXIT_ERRORS: u8 = 2;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let args = Args::parse();
+async fn main() -> ExitCode {
+    match run(Args::parse()).await {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::from(EXIT_ERRORS)
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<ExitCode> {
+    if let Some(command) = &args.command {
+        return run_command(command);
+    }
+
+    if args.list_snapshots {
+        list_snapshots(&args.config_name()?)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
     let config: Config = (&args).try_into()?;
     let requires_caching = config.requires_cache();
-    let cache_location = get_cache_location(&args.path);
-    if args.clear_cache {
-        let cache_location = cache_location.as_ref().unwrap_or_else(|e| {
-            panic!(
-                "Failed to handle cache location for path {}: {e:?}",
-                args.path.display()
+    let config_name = args.config_name()?;
+    let cache_location = get_cache_location(&config_name);
+    if args.clear_cache && cache_location.exists() {
+        remove_file(&cache_location).with_context(|| {
+            format!(
+                "Failed to clear cache for path {}",
+                cache_location.display()
             )
-        });
+        })?;
+    }
+    let mut client = Client::new();
+    client.set_limits(args.concurrency, config.rate_interval()?, config.sequential);
 
-        if cache_location.exists() {
-            remove_file(cache_location).with_context(|| {
+    if let Some(name) = &args.snapshot {
+        let snapshot_location = get_snapshot_location(&config_name, name)?;
+        if snapshot_location.exists() {
+            remove_file(&snapshot_location).with_context(|| {
                 format!(
-                    "Failed to clear cache for path {}",
-                    cache_location.display()
+                    "Failed to replace snapshot for path {}",
+                    snapshot_location.display()
                 )
             })?;
         }
-    }
-    let mut client = Client::new();
 
-    if requires_caching {
         client
-            .load_cache(cache_location?)
+            .load_cache(snapshot_location)
+            .context("Failed to create snapshot")?;
+    } else if requires_caching || args.tui || args.accept.is_some() || args.accept_interactive {
+        client
+            .load_cache(cache_location)
             .context("Failed to load cache")?;
     }
 
+    if let Some(name) = &args.against {
+        let left = get_snapshot_location(&config_name, name)?;
+        client.load_snapshots(Some(&left), None)?;
+    } else if let Some([left, right]) = args.compare_snapshots.as_deref() {
+        let left = get_snapshot_location(&config_name, left)?;
+        let right = get_snapshot_location(&config_name, right)?;
+        client.load_snapshots(Some(&left), Some(&right))?;
+    }
+
     if let Some(path) = args.out {
         save_responses_with_differences(client, config, path).await?;
-        return Ok(());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let stages = client::stages(&config.requests)?;
+    let mut runs = vec![];
+    let mut errors = vec![];
+    for _ in 0..args.repeat.max(1) {
+        let (run, run_errors) = get_responses(client.clone(), &stages).await;
+        runs.push(run);
+        errors = run_errors;
+    }
+
+    let latency = (runs.len() > 1).then(|| LatencySummary::new(&runs));
+    let responses = runs.pop().unwrap_or_default();
+    if args.tui {
+        tui::run(client, &config.requests, responses, errors, args.renderer).await?;
+        return Ok(ExitCode::SUCCESS);
     }
 
-    let responses = get_responses(client, config).await;
-    if !args.cache_only {
-        print_differences(&responses);
+    if args.accept.is_some() || args.accept_interactive {
+        accept_responses(&args, &client, &config.requests, &responses)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let report_to_stdout = args.report.is_some() && args.report_path.is_none();
+    if let Some(format) = args.report {
+        write_report(&args, format, &responses, &errors)?;
+    }
+
+    let exit_code = if args.check {
+        check(&responses, &errors, args.renderer, report_to_stdout)?
+    } else {
+        if !args.cache_only && !report_to_stdout {
+            print_differences(&responses, args.renderer)?;
+        }
+        ExitCode::SUCCESS
+    };
+
+    if let Some(latency) = latency
+        && !report_to_stdout
+    {
+        println!("{latency}");
     }
+
+    Ok(exit_code)
+}
+
+fn accept_responses(
+    args: &Args,
+    client: &Client,
+    requests: &[RequestsConfig],
+    responses: &[Response],
+) -> Result<()> {
+    let accepted = match &args.accept {
+        Some(pattern) => accept::accept_matching(client, requests, responses, pattern)?,
+        None => accept::accept_interactive(client, requests, responses, args.renderer)?,
+    };
+
+    println!("{accepted} accepted");
     Ok(())
 }
 
-fn print_differences(responses: &[Response]) {
+fn write_report(
+    args: &Args,
+    format: ReportFormat,
+    responses: &[Response],
+    errors: &[(String, anyhow::Error)],
+) -> Result<()> {
+    let suite = args.config_name()?;
+    let report = report::generate(format, &suite, responses, errors)?;
+    match &args.report_path {
+        Some(path) => std::fs::write(path, report)
+            .with_context(|| format!("Failed to write report to {}", path.display()))?,
+        None => println!("{report}"),
+    }
+
+    Ok(())
+}
+
+fn run_command(command: &Command) -> Result<ExitCode> {
+    match command {
+        Command::Generate(generate) => generate::generate(generate)?,
+        Command::Import(import) => import::import(import)?,
+        Command::Check(check) => return validate::validate(check),
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn check(
+    responses: &[Response],
+    errors: &[(String, anyhow::Error)],
+    renderer: Renderer,
+    quiet: bool,
+) -> Result<ExitCode> {
+    let different = responses
+        .iter()
+        .filter(|response| !response.is_equal())
+        .collect::<Vec<_>>();
+
+    if !quiet {
+        let (terminal_width, _) = term_size::dimensions().unwrap_or((100, 100));
+        for response in &different {
+            println!(
+                "{}",
+                diff::format_response(response, renderer, terminal_width)?
+            );
+        }
+
+        println!(
+            "{} equal, {} different, {} errored",
+            style(responses.len() - different.len()).green(),
+            style(different.len()).yellow(),
+            style(errors.len()).red()
+        );
+
+        for response in &different {
+            println!("{} {}", style("different:").yellow(), response.name);
+        }
+
+        for (name, _) in errors {
+            println!("{} {name}", style("errored:").red());
+        }
+    }
+
+    if !errors.is_empty() {
+        Ok(ExitCode::from(EXIT_ERRORS))
+    } else if !different.is_empty() {
+        Ok(ExitCode::from(EXIT_DIFFERENCES))
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn print_differences(responses: &[Response], renderer: Renderer) -> Result<()> {
     let (terminal_width, _) = term_size::dimensions().unwrap_or((100, 100));
     let diff = responses
         .iter()
-        .map(|response| {
-            if response.left.text == response.right.text {
-                format!(
-                    "{}: {} == {}\n",
-                    response.name, response.left.url, response.right.url
-                )
-            } else {
-                format!(
-                    "{}: {} => {}\n{}",
-                    response.name,
-                    response.left.url,
-                    response.right.url,
-                    get_delta_result(&response.left.text, &response.right.text, terminal_width)
-                )
-            }
-        })
-        .collect::<String>();
+        .map(|response| diff::format_response(response, renderer, terminal_width))
+        .collect::<Result<String>>()?;
 
     PrettyPrinter::new()
         .input_from_bytes(diff.as_bytes())
         .paging_mode(bat::PagingMode::QuitIfOneScreen)
         .print()
         .expect("Failed to show differences using bat");
+
+    Ok(())
 }
 
-async fn get_responses(client: Client, config: Config) -> Vec<Response> {
-    let mut handles = vec![];
-    let progress_bar = ProgressBar::new(config.requests.len() as u64);
+/// Runs every stage of requests one after the other, the requests of a stage in parallel. Returns
+/// the responses and the errors of the requests that failed along with their names.
+async fn get_responses(
+    client: Client,
+    stages: &[Vec<RequestsConfig>],
+) -> (Vec<Response>, Vec<(String, anyhow::Error)>) {
+    let progress_bar = ProgressBar::new(stages.iter().map(Vec::len).sum::<usize>() as u64);
     progress_bar.set_style(
         ProgressStyle::with_template("[{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>7}/{len:7}")
             .unwrap(),
     );
 
-    for request in config.requests {
-        let mut moved_client = client.clone();
-        let moved_progress_bar = progress_bar.clone();
-        let handle = tokio::spawn(async move {
-            let result = moved_client.get_response(&request).await;
-            moved_progress_bar.inc(1);
-            result
-        });
+    let mut responses = vec![];
+    let mut errors = vec![];
+    for requests in stages {
+        let mut handles = vec![];
+        for request in requests.iter().cloned() {
+            let mut moved_client = client.clone();
+            let moved_progress_bar = progress_bar.clone();
+            let name = request.name.clone();
+            let handle = tokio::spawn(async move {
+                let result = moved_client.get_response(&request).await;
+                moved_progress_bar.inc(1);
+                result
+            });
 
-        handles.push(handle);
-    }
+            handles.push((name, handle));
+        }
 
-    let mut responses = vec![];
-    for handle in handles {
-        let result = handle.await.expect("Failed to unlock ansync handle");
-        match result {
-            Ok(response) => {
-                responses.push(response);
+        for (name, handle) in handles {
+            let result = handle
+                .await
+                .unwrap_or_else(|e| Err(anyhow!("Request {name} failed unexpectedly: {e}")));
+
+            match result {
+                Ok(response) => {
+                    responses.push(response);
+                }
+                Err(e) => {
+                    eprintln!("{e:?}");
+                    errors.push((name, e));
+                }
             }
-            Err(e) => eprintln!("{e:?}"),
         }
     }
 
     progress_bar.finish();
-    responses
+    (responses, errors)
 }
 
 async fn save_responses_with_differences(
@@ -130,79 +308,75 @@ async fn save_responses_with_differences(
     config: Config,
     path: PathBuf,
 ) -> Result<()> {
-    let mut handles = vec![];
-    let progress_bar = ProgressBar::new(config.requests.len() as u64);
-    progress_bar.set_style(
-        ProgressStyle::with_template("[{elapsed_precise}] {wide_bar:.cyan/blue} {pos:>7}/{len:7}")
-            .unwrap(),
-    );
+    let stages = client::stages(&config.requests)?;
+    let (responses, _) = get_responses(client, &stages).await;
+    // The requests extracting the captures of a different request are kept for it to run.
+    let different = config
+        .requests
+        .iter()
+        .enumerate()
+        .filter(|(_, request)| {
+            responses
+                .iter()
+                .any(|response| response.name == request.name && !response.is_equal())
+        })
+        .map(|(index, _)| index);
+    let kept = client::captures::with_providers(&config.requests, different)?;
+    let requests = config
+        .requests
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| kept.contains(index))
+        .map(|(_, request)| request)
+        .collect::<Vec<_>>();
 
-    for request in config.requests {
-        let mut moved_client = client.clone();
-        let moved_progress_bar = progress_bar.clone();
-        let handle = tokio::spawn(async move {
-            let result = moved_client.get_response(&request).await;
-            match result {
-                Ok(response) => {
-                    moved_progress_bar.inc(1);
-                    Ok((request, response))
-                }
-                Err(e) => {
-                    moved_progress_bar.inc(1);
-                    Err(anyhow!("{e}"))
-                }
-            }
-        });
+    let config = Config {
+        rate_limit: config.rate_limit,
+        sequential: config.sequential,
+        ..Config::from(requests)
+    };
+    generate::write(&config, Some(&path))
+}
+
+fn get_cache_location(config_name: &str) -> PathBuf {
+    Path::new("./cache").join(config_name)
+}
 
-        handles.push(handle);
+fn get_snapshots_location(config_name: &str) -> PathBuf {
+    Path::new("./cache").join("snapshots").join(config_name)
+}
+
+fn get_snapshot_location(config_name: &str, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(anyhow!("Invalid snapshot name {name}"));
     }
 
-    let mut requests = vec![];
-    for handle in handles {
-        let result = handle.await.expect("Failed to unlock ansync handle");
-        match result {
-            Ok((request, response)) => {
-                if response.left.text != response.right.text {
-                    requests.push(request);
-                }
-            }
-            Err(e) => eprintln!("{e:?}"),
-        }
+    Ok(get_snapshots_location(config_name).join(format!("{name}.json")))
+}
+
+fn list_snapshots(config_name: &str) -> Result<()> {
+    let location = get_snapshots_location(config_name);
+    if !location.exists() {
+        println!("No snapshots recorded for {config_name}");
+        return Ok(());
     }
 
-    let config = toml::to_string(&Config::from(requests))?;
-    let mut file = File::create(path)?;
-    file.write_all(config.as_bytes())?;
+    let mut names = std::fs::read_dir(&location)
+        .with_context(|| format!("Failed to read snapshots in {}", location.display()))?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "json" {
+                return None;
+            }
 
-    progress_bar.finish();
-    Ok(())
-}
+            Some(path.file_stem()?.to_string_lossy().into_owned())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
 
-fn get_cache_location(path: &Path) -> Result<PathBuf> {
-    Ok(Path::new("./cache").join(path.file_name().context("Failed to retreive file name")?))
-}
+    for name in names {
+        println!("{name}");
+    }
 
-fn get_delta_result(left: &str, right: &str, width: usize) -> String {
-    let mut left_file = NamedTempFile::new().expect("Failed to create temp file");
-    let () = left_file
-        .write_all(left.as_bytes())
-        .expect("Failed to write to temp file");
-
-    let mut right_file = NamedTempFile::new().expect("Failed to create temp file");
-    let () = right_file
-        .write_all(right.as_bytes())
-        .expect("Failed to write to temp file");
-
-    Command::new("delta")
-        .arg(left_file.path())
-        .arg(right_file.path())
-        .arg("--default-language=json")
-        .arg(format!("--width={width}"))
-        .arg("--file-style=omit")
-        .arg("-s")
-        .output()
-        .ok()
-        .map(|output| output.stdout)
-        .and_then(|out| String::from_utf8(out).ok())
-        .expect("Failed to run delta, make sure you have `git-delta` from `https://github.com/dandavison/delta` installed")
+    Ok(())
 }
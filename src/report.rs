@@ -0,0 +1,298 @@
+use std::{fmt::Write as _, time::Duration};
+
+use anyhow::{Error, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    client::Response,
+    diff::{self, Change, Renderer},
+};
+
+#[allow(clippy::doc_markdown)]
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum ReportFormat {
+    /// Per request results with a structured list of differences
+    Json,
+    /// JUnit XML, each request is a test case and its differences the failure message
+    Junit,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    equal: usize,
+    different: usize,
+    errored: usize,
+    requests: Vec<RequestReport<'a>>,
+    errors: Vec<ErrorReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct RequestReport<'a> {
+    name: &'a str,
+    equal: bool,
+    elapsed_ms: u128,
+    left: SideReport<'a>,
+    right: SideReport<'a>,
+    differences: Vec<DifferenceReport>,
+}
+
+#[derive(Serialize)]
+struct SideReport<'a> {
+    url: &'a str,
+    status_code: u16,
+    attempts: u32,
+    cached: bool,
+    elapsed_ms: u128,
+    time_to_first_byte_ms: u128,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum DifferenceReport {
+    StatusCode {
+        left: u16,
+        right: u16,
+    },
+    ExpectedStatus {
+        message: String,
+    },
+    Header {
+        name: String,
+        left: Option<String>,
+        right: Option<String>,
+    },
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        left: Value,
+        right: Value,
+    },
+    /// Bodies that are not JSON and can only be compared as a whole.
+    Body,
+    Latency {
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+struct ErrorReport<'a> {
+    name: &'a str,
+    error: String,
+}
+
+/// Generates a report of the compared responses and the requests that failed in the given format.
+pub fn generate(
+    format: ReportFormat,
+    suite: &str,
+    responses: &[Response],
+    errors: &[(String, Error)],
+) -> Result<String> {
+    match format {
+        ReportFormat::Json => json(responses, errors),
+        ReportFormat::Junit => junit(suite, responses, errors),
+    }
+}
+
+fn json(responses: &[Response], errors: &[(String, Error)]) -> Result<String> {
+    let requests = responses.iter().map(request_report).collect::<Vec<_>>();
+    let different = requests.iter().filter(|request| !request.equal).count();
+    let report = Report {
+        equal: requests.len() - different,
+        different,
+        errored: errors.len(),
+        requests,
+        errors: errors
+            .iter()
+            .map(|(name, error)| ErrorReport {
+                name,
+                error: format!("{error:#}"),
+            })
+            .collect(),
+    };
+
+    Ok(serde_json::to_string_pretty(&report)?)
+}
+
+fn request_report(response: &Response) -> RequestReport<'_> {
+    let mut differences = vec![];
+    if response.left.status_code != response.right.status_code {
+        differences.push(DifferenceReport::StatusCode {
+            left: response.left.status_code,
+            right: response.right.status_code,
+        });
+    }
+
+    differences.extend(response.status_errors.iter().map(|message| {
+        DifferenceReport::ExpectedStatus {
+            message: message.clone(),
+        }
+    }));
+
+    differences.extend(
+        response
+            .header_differences
+            .iter()
+            .map(|header| DifferenceReport::Header {
+                name: header.name.clone(),
+                left: header.left.clone(),
+                right: header.right.clone(),
+            }),
+    );
+
+    if let Some(message) = &response.latency_error {
+        differences.push(DifferenceReport::Latency {
+            message: message.clone(),
+        });
+    }
+
+    if !response.is_body_equal() {
+        match (
+            serde_json::from_str::<Value>(&response.left.text),
+            serde_json::from_str::<Value>(&response.right.text),
+        ) {
+            (Ok(left), Ok(right)) => {
+                differences.extend(diff::json_differences(&left, &right).into_iter().map(
+                    |difference| match difference.change {
+                        Change::Added(value) => DifferenceReport::Added {
+                            path: difference.path,
+                            value,
+                        },
+                        Change::Removed(value) => DifferenceReport::Removed {
+                            path: difference.path,
+                            value,
+                        },
+                        Change::Changed { left, right } => DifferenceReport::Changed {
+                            path: difference.path,
+                            left,
+                            right,
+                        },
+                    },
+                ));
+            }
+            _ => differences.push(DifferenceReport::Body),
+        }
+    }
+
+    RequestReport {
+        name: &response.name,
+        equal: response.is_equal(),
+        elapsed_ms: response.elapsed.as_millis(),
+        left: SideReport {
+            url: &response.left.url,
+            status_code: response.left.status_code,
+            attempts: response.left.attempts,
+            cached: response.left.cached,
+            elapsed_ms: response.left.elapsed.as_millis(),
+            time_to_first_byte_ms: response.left.time_to_first_byte.as_millis(),
+        },
+        right: SideReport {
+            url: &response.right.url,
+            status_code: response.right.status_code,
+            attempts: response.right.attempts,
+            cached: response.right.cached,
+            elapsed_ms: response.right.elapsed.as_millis(),
+            time_to_first_byte_ms: response.right.time_to_first_byte.as_millis(),
+        },
+        differences,
+    }
+}
+
+fn junit(suite: &str, responses: &[Response], errors: &[(String, Error)]) -> Result<String> {
+    let failures = responses
+        .iter()
+        .filter(|response| !response.is_equal())
+        .count();
+    let time = responses
+        .iter()
+        .map(|response| response.elapsed)
+        .sum::<Duration>()
+        .as_secs_f64();
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        out,
+        "<testsuites name=\"curpare\" tests=\"{}\" failures=\"{failures}\" errors=\"{}\" time=\"{time:.3}\">",
+        responses.len() + errors.len(),
+        errors.len(),
+    )?;
+    writeln!(
+        out,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{failures}\" errors=\"{}\" time=\"{time:.3}\">",
+        escape(suite),
+        responses.len() + errors.len(),
+        errors.len(),
+    )?;
+
+    for response in responses {
+        let name = escape(&response.name);
+        let time = response.elapsed.as_secs_f64();
+        if response.is_equal() {
+            writeln!(
+                out,
+                "    <testcase name=\"{name}\" classname=\"{}\" time=\"{time:.3}\"/>",
+                escape(suite)
+            )?;
+            continue;
+        }
+
+        let message =
+            console::strip_ansi_codes(&diff::format_response(response, Renderer::Unified, 0)?)
+                .into_owned();
+        writeln!(
+            out,
+            "    <testcase name=\"{name}\" classname=\"{}\" time=\"{time:.3}\">",
+            escape(suite)
+        )?;
+        writeln!(
+            out,
+            "      <failure message=\"Responses differ\">{}</failure>",
+            escape(&message)
+        )?;
+        writeln!(out, "    </testcase>")?;
+    }
+
+    for (name, error) in errors {
+        writeln!(
+            out,
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"0.000\">",
+            escape(name),
+            escape(suite)
+        )?;
+        writeln!(
+            out,
+            "      <error message=\"{}\">{}</error>",
+            escape(&error.to_string()),
+            escape(&format!("{error:?}"))
+        )?;
+        writeln!(out, "    </testcase>")?;
+    }
+
+    writeln!(out, "  </testsuite>")?;
+    writeln!(out, "</testsuites>")?;
+    Ok(out)
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .filter(|&c| matches!(c, '\t' | '\n' | '\r') || !c.is_control())
+        .fold(String::with_capacity(text.len()), |mut out, c| {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&apos;"),
+                c => out.push(c),
+            }
+            out
+        })
+}
@@ -0,0 +1,321 @@
+use std::{collections::HashMap, ops::Range, path::Path};
+
+use anyhow::{Context, Error, Result, anyhow, bail};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct VarsSection {
+    #[serde(default)]
+    vars: HashMap<String, String>,
+}
+
+/// Expands the placeholders of a TOML configuration.
+///
+/// - `${NAME}` is replaced by the variable, failing when it is not set.
+/// - `${NAME:-default}` falls back to `default` when the variable is not set or empty.
+/// - `${NAME:?message}` fails with `message` when the variable is not set or empty.
+/// - `$$` is replaced by a single `$`, any other `$` is kept as is.
+///
+/// Comments are kept as is, a commented out line doesn't need its variables to be set.
+///
+/// Variables are looked up in the `[vars]` table of the configuration first and then in the
+/// environment. Values of the `[vars]` table can themselves reference environment variables.
+pub fn render(path: &Path, toml: &str, envs: &HashMap<String, String>) -> Result<String> {
+    let (rendered, unresolved) = render_all(path, toml, envs)?;
+    match unresolved.into_iter().next() {
+        Some((line, error)) => Err(error.context(format!(
+            "Failed to expand variables at {}:{line}",
+            path.display()
+        ))),
+        None => Ok(rendered),
+    }
+}
+
+/// Expands the placeholders like [`render`] without stopping at the first line that can't be
+/// expanded. Such lines are kept as is and returned with their line number and error.
+pub fn render_all(
+    path: &Path,
+    toml: &str,
+    envs: &HashMap<String, String>,
+) -> Result<(String, Vec<(usize, Error)>)> {
+    let lines: Vec<&str> = toml.split_inclusive('\n').collect();
+    let section = vars_section(&lines);
+    let mut unresolved = vec![];
+
+    let vars_toml = expand_lines(
+        &lines,
+        section.clone(),
+        &|name| envs.get(name),
+        &mut unresolved,
+    );
+    let vars = toml::from_str::<VarsSection>(&vars_toml)
+        .with_context(|| format!("The [vars] table of {} is not valid", path.display()))?
+        .vars;
+
+    let lookup = |name: &str| vars.get(name).or_else(|| envs.get(name));
+    let before = expand_lines(&lines, 0..section.start, &lookup, &mut unresolved);
+    let after = expand_lines(&lines, section.end..lines.len(), &lookup, &mut unresolved);
+    unresolved.sort_by_key(|(line, _)| *line);
+
+    Ok((before + &vars_toml + &after, unresolved))
+}
+
+/// Range of lines of the `[vars]` table, from its header up to the next table.
+fn vars_section(lines: &[&str]) -> Range<usize> {
+    let Some(start) = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with("[vars]"))
+    else {
+        return 0..0;
+    };
+
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |offset| start + 1 + offset);
+
+    start..end
+}
+
+fn expand_lines<'a>(
+    lines: &[&str],
+    range: Range<usize>,
+    lookup: &impl Fn(&str) -> Option<&'a String>,
+    unresolved: &mut Vec<(usize, Error)>,
+) -> String {
+    let start = range.start;
+    let mut multiline = None;
+    lines[range]
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let (code, comment) =
+                line.split_at(comment_start(line, &mut multiline).unwrap_or(line.len()));
+            expand(code, lookup).map_or_else(
+                |error| {
+                    unresolved.push((start + index + 1, error));
+                    (*line).to_string()
+                },
+                |code| code + comment,
+            )
+        })
+        .collect()
+}
+
+/// Byte offset of the comment of a TOML line. `multiline` is the delimiter of the multi-line
+/// string the line starts in, if any, and is updated for the next line.
+fn comment_start(line: &str, multiline: &mut Option<&'static str>) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        if let Some(delimiter) = *multiline {
+            index = string_end(bytes, index, delimiter)?;
+            *multiline = None;
+            continue;
+        }
+
+        match bytes[index] {
+            b'#' => return Some(index),
+            b'"' if bytes[index..].starts_with(b"\"\"\"") => {
+                *multiline = Some("\"\"\"");
+                index += 3;
+            }
+            b'\'' if bytes[index..].starts_with(b"'''") => {
+                *multiline = Some("'''");
+                index += 3;
+            }
+            b'"' => index = string_end(bytes, index + 1, "\"").unwrap_or(bytes.len()),
+            b'\'' => index = string_end(bytes, index + 1, "'").unwrap_or(bytes.len()),
+            _ => index += 1,
+        }
+    }
+
+    None
+}
+
+/// Offset right after the delimiter closing a string, escapes only apply to basic strings.
+fn string_end(bytes: &[u8], mut index: usize, delimiter: &str) -> Option<usize> {
+    while index < bytes.len() {
+        if delimiter.starts_with('"') && bytes[index] == b'\\' {
+            index += 2;
+        } else if bytes[index..].starts_with(delimiter.as_bytes()) {
+            return Some(index + delimiter.len());
+        } else {
+            index += 1;
+        }
+    }
+
+    None
+}
+
+fn expand<'a>(text: &str, lookup: &impl Fn(&str) -> Option<&'a String>) -> Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        match chars.peek() {
+            Some('$') => {
+                chars.next();
+                expanded.push('$');
+            }
+            Some('{') => {
+                chars.next();
+                let mut depth = 1;
+                let placeholder: String = chars
+                    .by_ref()
+                    .take_while(|&char| {
+                        match char {
+                            '{' => depth += 1,
+                            '}' => depth -= 1,
+                            _ => {}
+                        }
+                        depth > 0
+                    })
+                    .collect();
+
+                if depth > 0 {
+                    bail!(
+                        "Placeholder `${{{}` is missing its closing `}}`",
+                        placeholder.trim_end()
+                    );
+                }
+
+                expanded.push_str(&substitute(&placeholder, lookup)?);
+            }
+            _ => expanded.push('$'),
+        }
+    }
+
+    Ok(expanded)
+}
+
+fn substitute<'a>(
+    placeholder: &str,
+    lookup: &impl Fn(&str) -> Option<&'a String>,
+) -> Result<String> {
+    let (name, operator, argument) = match placeholder.split_once(':') {
+        Some((name, rest)) if rest.starts_with(['-', '?']) => (name, &rest[..1], &rest[1..]),
+        _ => (placeholder, "", ""),
+    };
+
+    let value = lookup(name).filter(|value| operator.is_empty() || !value.is_empty());
+    match (value, operator) {
+        (Some(value), _) => Ok(value.clone()),
+        (None, "-") => expand(argument, lookup),
+        (None, "?") if !argument.is_empty() => {
+            Err(anyhow!("{name}: {}", expand(argument, lookup)?))
+        }
+        _ => bail!(
+            "Variable {name} is not set. Make sure to provide it, add it to `.env` or to the [vars] table"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envs(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+            .collect()
+    }
+
+    fn render_str(toml: &str, pairs: &[(&str, &str)]) -> Result<String> {
+        render(Path::new("test.toml"), toml, &envs(pairs))
+    }
+
+    #[test]
+    fn replaces_variables() {
+        let rendered = render_str("url = \"${HOST}/users\"\n", &[("HOST", "http://a")]).unwrap();
+        assert_eq!(rendered, "url = \"http://a/users\"\n");
+    }
+
+    #[test]
+    fn fails_on_unset_variables_with_their_line() {
+        let error = render_str("a = 1\nurl = \"${HOST}\"\n", &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to expand variables at test.toml:2"
+        );
+        assert!(format!("{error:#}").contains("Variable HOST is not set"));
+    }
+
+    #[test]
+    fn falls_back_to_defaults_when_unset_or_empty() {
+        let toml = "a = \"${A:-one}\"\nb = \"${B:-${A:-two}}\"\nc = \"${C:-x}\"\n";
+        let rendered = render_str(toml, &[("B", ""), ("C", "set")]).unwrap();
+        assert_eq!(rendered, "a = \"one\"\nb = \"two\"\nc = \"set\"\n");
+    }
+
+    #[test]
+    fn fails_with_the_message_of_required_variables() {
+        let error =
+            render_str("a = \"${TOKEN:?TOKEN must be set}\"\n", &[("TOKEN", "")]).unwrap_err();
+        assert_eq!(error.root_cause().to_string(), "TOKEN: TOKEN must be set");
+
+        let rendered = render_str("a = \"${TOKEN:?unused}\"\n", &[("TOKEN", "t")]).unwrap();
+        assert_eq!(rendered, "a = \"t\"\n");
+    }
+
+    #[test]
+    fn escapes_dollars() {
+        let rendered = render_str("a = \"$${HOST} costs $5\"\n", &[("HOST", "h")]).unwrap();
+        assert_eq!(rendered, "a = \"${HOST} costs $5\"\n");
+    }
+
+    #[test]
+    fn reports_unclosed_placeholders() {
+        let error = render_str("a = \"${HOST\"\n", &[("HOST", "h")]).unwrap_err();
+        assert!(
+            error
+                .root_cause()
+                .to_string()
+                .contains("missing its closing")
+        );
+    }
+
+    #[test]
+    fn prefers_vars_over_the_environment() {
+        let toml = "url = \"${HOST}\"\n\n[vars]\nHOST = \"${ENV_HOST}\"\n";
+        let rendered = render_str(toml, &[("HOST", "env"), ("ENV_HOST", "vars")]).unwrap();
+        assert!(rendered.starts_with("url = \"vars\"\n"));
+    }
+
+    #[test]
+    fn leaves_comments_unexpanded() {
+        let toml = "# url = \"${UNSET:?set me}\"\nname = \"a # ${A}\" # ${UNSET}\n";
+        let rendered = render_str(toml, &[("A", "b")]).unwrap();
+        assert_eq!(
+            rendered,
+            "# url = \"${UNSET:?set me}\"\nname = \"a # b\" # ${UNSET}\n"
+        );
+    }
+
+    #[test]
+    fn expands_multi_line_strings_with_hashes() {
+        let toml = "body = \"\"\"\n# ${A}\n\"\"\" # ${UNSET}\nb = '#' # ${UNSET}\n";
+        let rendered = render_str(toml, &[("A", "a")]).unwrap();
+        assert_eq!(
+            rendered,
+            "body = \"\"\"\n# a\n\"\"\" # ${UNSET}\nb = '#' # ${UNSET}\n"
+        );
+    }
+
+    #[test]
+    fn collects_every_unresolved_line() {
+        let (_, unresolved) = render_all(
+            Path::new("test.toml"),
+            "a = \"${A}\"\nb = 1\nc = \"${C}\"\n",
+            &envs(&[]),
+        )
+        .unwrap();
+        let lines = unresolved.iter().map(|(line, _)| *line).collect::<Vec<_>>();
+        assert_eq!(lines, [1, 3]);
+    }
+}
@@ -0,0 +1,383 @@
+use std::fmt::Display;
+
+use ansi_to_tui::IntoText;
+use anyhow::Result;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+};
+
+use crate::{
+    client::{Client, RequestsConfig, Response},
+    diff::{Renderer, format_response},
+};
+
+const HELP: &str =
+    "↑↓ select  PgUp/PgDn scroll  / filter  f status  i ignore rules  r re-run  a accept  q quit";
+
+/// Browses the results of a run, one request at a time.
+pub async fn run(
+    client: Client,
+    requests: &[RequestsConfig],
+    mut responses: Vec<Response>,
+    mut errors: Vec<(String, anyhow::Error)>,
+    renderer: Renderer,
+) -> Result<()> {
+    let entries = requests
+        .iter()
+        .map(|request| {
+            let outcome = if let Some(index) = responses
+                .iter()
+                .position(|response| response.name == request.name)
+            {
+                Outcome::Response(Box::new(responses.remove(index)))
+            } else if let Some(index) = errors.iter().position(|(name, _)| *name == request.name) {
+                Outcome::Error(format!("{:?}", errors.remove(index).1))
+            } else {
+                Outcome::Error("The request did not run".to_string())
+            };
+
+            Entry {
+                request: request.clone(),
+                outcome,
+                skip_ignore: false,
+            }
+        })
+        .collect();
+
+    let mut app = App {
+        entries,
+        client,
+        renderer,
+        filter: String::new(),
+        editing_filter: false,
+        status_filter: StatusFilter::All,
+        list_state: ListState::default().with_selected(Some(0)),
+        scroll: 0,
+        message: HELP.to_string(),
+        diff: None,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal).await;
+    ratatui::restore();
+    result
+}
+
+struct Entry {
+    request: RequestsConfig,
+    outcome: Outcome,
+    skip_ignore: bool,
+}
+
+enum Outcome {
+    Response(Box<Response>),
+    Error(String),
+}
+
+impl Entry {
+    /// The request as it runs, without its ignore rules when they are toggled off.
+    fn request(&self) -> RequestsConfig {
+        let mut request = self.request.clone();
+        if self.skip_ignore {
+            for part in [&mut request.left, &mut request.right] {
+                part.ignore_lines.clear();
+                part.ignore_paths.clear();
+            }
+        }
+        request
+    }
+
+    fn status_message(&self) -> String {
+        format!(
+            "{} is {}{}",
+            self.request.name,
+            self.status(),
+            if self.skip_ignore {
+                ", ignore rules are off"
+            } else {
+                ""
+            }
+        )
+    }
+
+    fn status(&self) -> StatusFilter {
+        match &self.outcome {
+            Outcome::Response(response) if response.is_equal() => StatusFilter::Equal,
+            Outcome::Response(_) => StatusFilter::Different,
+            Outcome::Error(_) => StatusFilter::Errored,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum StatusFilter {
+    All,
+    Different,
+    Errored,
+    Equal,
+}
+
+impl StatusFilter {
+    fn next(self) -> Self {
+        match self {
+            StatusFilter::All => StatusFilter::Different,
+            StatusFilter::Different => StatusFilter::Errored,
+            StatusFilter::Errored => StatusFilter::Equal,
+            StatusFilter::Equal => StatusFilter::All,
+        }
+    }
+}
+
+impl Display for StatusFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            StatusFilter::All => "all",
+            StatusFilter::Different => "different",
+            StatusFilter::Errored => "errored",
+            StatusFilter::Equal => "equal",
+        };
+        write!(f, "{name}")
+    }
+}
+
+struct App {
+    entries: Vec<Entry>,
+    client: Client,
+    renderer: Renderer,
+    filter: String,
+    editing_filter: bool,
+    status_filter: StatusFilter,
+    list_state: ListState,
+    scroll: u16,
+    message: String,
+
+    /// Rendered diff of an entry for a width, kept until the selection, the width or the entry
+    /// change.
+    diff: Option<(usize, u16, Text<'static>)>,
+}
+
+impl App {
+    async fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = tokio::task::block_in_place(event::read)? else {
+                continue;
+            };
+
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if self.editing_filter {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Esc => self.editing_filter = false,
+                    KeyCode::Backspace => {
+                        self.filter.pop();
+                    }
+                    KeyCode::Char(c) => self.filter.push(c),
+                    _ => {}
+                }
+                self.select(0);
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.select(1),
+                KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+                KeyCode::PageDown | KeyCode::Char(' ') => {
+                    self.scroll = self.scroll.saturating_add(10);
+                }
+                KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+                KeyCode::Char('/') => self.editing_filter = true,
+                KeyCode::Char('f') => {
+                    self.status_filter = self.status_filter.next();
+                    self.select(0);
+                }
+                KeyCode::Char('i') => {
+                    if let Some(index) = self.selected() {
+                        self.toggle_ignore(index);
+                    }
+                }
+                KeyCode::Char('r') => {
+                    if let Some(index) = self.selected() {
+                        self.rerun(terminal, index).await?;
+                    }
+                }
+                KeyCode::Char('a') => {
+                    if let Some(index) = self.selected() {
+                        self.accept(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Indices of the entries matching the name and status filters.
+    fn visible(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| {
+                (self.status_filter == StatusFilter::All || entry.status() == self.status_filter)
+                    && entry.request.name.to_lowercase().contains(&filter)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.visible().get(self.list_state.selected()?).copied()
+    }
+
+    /// Moves the selection by `offset` within the visible entries, keeping it in range.
+    fn select(&mut self, offset: isize) {
+        let count = self.visible().len();
+        let selected = self
+            .list_state
+            .selected()
+            .unwrap_or_default()
+            .saturating_add_signed(offset)
+            .min(count.saturating_sub(1));
+        self.list_state.select((count > 0).then_some(selected));
+        self.scroll = 0;
+    }
+
+    async fn rerun(&mut self, terminal: &mut DefaultTerminal, index: usize) -> Result<()> {
+        let request = self.entries[index].request();
+        self.message = format!("Running {}...", request.name);
+        terminal.draw(|frame| self.draw(frame))?;
+
+        let entry = &mut self.entries[index];
+        entry.outcome = match self.client.get_response(&request).await {
+            Ok(response) => Outcome::Response(Box::new(response)),
+            Err(e) => Outcome::Error(format!("{e:?}")),
+        };
+        self.message = entry.status_message();
+        self.diff = None;
+        Ok(())
+    }
+
+    /// Turns the ignore rules of an entry off or back on, comparing the responses it already has
+    /// again rather than sending the request another time.
+    fn toggle_ignore(&mut self, index: usize) {
+        let entry = &mut self.entries[index];
+        entry.skip_ignore = !entry.skip_ignore;
+
+        if let Outcome::Response(response) = &entry.outcome
+            && let (Some(left), Some(right)) = (&response.raw_left, &response.raw_right)
+        {
+            let elapsed = response.elapsed;
+            match Client::compare(&entry.request(), left.clone(), right.clone()) {
+                Ok(mut compared) => {
+                    compared.elapsed = elapsed;
+                    entry.outcome = Outcome::Response(Box::new(compared));
+                }
+                Err(e) => entry.outcome = Outcome::Error(format!("{e:?}")),
+            }
+        }
+
+        self.message = entry.status_message();
+        self.diff = None;
+    }
+
+    fn accept(&mut self, index: usize) {
+        let entry = &self.entries[index];
+        self.message = match &entry.outcome {
+            Outcome::Response(response) => match self.client.accept(&entry.request, response) {
+                Ok(()) => format!(
+                    "Accepted the right side of {} as its left side in the cache",
+                    entry.request.name
+                ),
+                Err(e) => format!("Failed to accept {}: {e}", entry.request.name),
+            },
+            Outcome::Error(_) => format!("{} has no response to accept", entry.request.name),
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, footer] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, diff_area] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)])
+                .areas(main);
+
+        let visible = self.visible();
+        let items = visible.iter().map(|&index| {
+            let entry = &self.entries[index];
+            let (marker, color) = match entry.status() {
+                StatusFilter::Equal => ("= ", Color::Green),
+                StatusFilter::Errored => ("! ", Color::Red),
+                _ => ("≠ ", Color::Yellow),
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(marker, Style::new().fg(color)),
+                Span::raw(entry.request.name.clone()),
+            ]))
+        });
+
+        let list = List::new(items)
+            .block(Block::bordered().title(format!(
+                " Requests {}/{} ({}) ",
+                visible.len(),
+                self.entries.len(),
+                self.status_filter
+            )))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+
+        let selected = self.selected();
+        let title = selected.map_or_else(String::new, |index| {
+            format!(" {} ", self.entries[index].request.name)
+        });
+        let width = diff_area.width.saturating_sub(2);
+        let text = match selected {
+            Some(index) => self.diff_text(index, width),
+            None => Text::raw("No request matches the filters"),
+        };
+        frame.render_widget(
+            Paragraph::new(text)
+                .block(Block::bordered().title(title))
+                .scroll((self.scroll, 0)),
+            diff_area,
+        );
+
+        let footer_text = if self.editing_filter {
+            format!("/{}█", self.filter)
+        } else {
+            self.message.clone()
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+
+    fn diff_text(&mut self, index: usize, width: u16) -> Text<'static> {
+        if let Some((cached_index, cached_width, text)) = &self.diff
+            && *cached_index == index
+            && *cached_width == width
+        {
+            return text.clone();
+        }
+
+        let text = match &self.entries[index].outcome {
+            Outcome::Response(response) => {
+                match format_response(response, self.renderer, width.into()) {
+                    Ok(diff) => diff.into_text().unwrap_or_else(|_| {
+                        Text::raw(console::strip_ansi_codes(&diff).into_owned())
+                    }),
+                    Err(e) => Text::raw(format!("Failed to render differences: {e:?}")).red(),
+                }
+            }
+            Outcome::Error(error) => Text::raw(error.clone()).red(),
+        };
+
+        self.diff = Some((index, width, text.clone()));
+        text
+    }
+}
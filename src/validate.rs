@@ -0,0 +1,644 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, hash_map::Entry},
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use anyhow::{Context, Result};
+use console::style;
+use dotenv::dotenv;
+use reqwest::{
+    Url,
+    header::{HeaderName, HeaderValue},
+};
+use serde::Deserialize;
+use serde_json_path::JsonPath;
+use toml::Spanned;
+
+use crate::{
+    args::CheckArgs,
+    client::{
+        self, Auth, BearerToken, Config, PartDefaults, PartRequestConfig, RequestsConfig, captures,
+        check_retries, check_status, timeout_duration,
+    },
+    load, template,
+};
+
+/// Methods a request can use, anything else is most likely a typo.
+const METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS", "TRACE", "CONNECT",
+];
+
+/// Value given to every capture while checking the requests referencing them.
+const SAMPLE_CAPTURE: &str = "capture";
+
+/// Checks configs and the configs they include without sending any request and prints every
+/// problem found with its line. Exits with 1 when there is any.
+pub fn validate(args: &CheckArgs) -> Result<ExitCode> {
+    dotenv().ok();
+    let envs: HashMap<String, String> = std::env::vars().collect();
+
+    let mut sources = vec![];
+    let mut seen = HashSet::new();
+    for path in &args.paths {
+        collect(
+            path,
+            &Config::default(),
+            &envs,
+            &mut vec![],
+            &mut seen,
+            &mut sources,
+        )?;
+    }
+
+    let requests = sources
+        .iter()
+        .filter_map(|source| source.parsed.as_ref())
+        .flat_map(|parsed| parsed.config.requests.iter().cloned())
+        .collect::<Vec<_>>();
+    let sample_captures = |side: fn(&RequestsConfig) -> &PartRequestConfig| {
+        requests
+            .iter()
+            .flat_map(|request| side(request).extract.keys())
+            .map(|name| (name.clone(), SAMPLE_CAPTURE.to_string()))
+            .collect::<HashMap<_, _>>()
+    };
+    let captures = [
+        sample_captures(|request| &request.left),
+        sample_captures(|request| &request.right),
+    ];
+
+    let mut names = HashMap::new();
+    let mut count = 0;
+    for source in sources {
+        let mut checker = Checker {
+            toml: &source.toml,
+            path: &source.path,
+            config_dir: source.path.parent().unwrap_or_else(|| Path::new("")),
+            problems: source.problems,
+            unresolved: &source.unresolved,
+        };
+        if let Some(parsed) = &source.parsed {
+            checker.check(parsed, &mut names, &captures);
+        }
+
+        let mut problems = checker.problems;
+        problems.sort_by_key(|problem| problem.line);
+        for problem in &problems {
+            match problem.line {
+                Some(line) => println!("{}:{line}: {}", source.path.display(), problem.message),
+                None => println!("{}: {}", source.path.display(), problem.message),
+            }
+        }
+        count += problems.len();
+    }
+
+    if let Err(error) = client::stages(&requests) {
+        println!("{error:#}");
+        count += 1;
+    }
+
+    let paths = args
+        .paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if count == 0 {
+        println!(
+            "{} {paths} {} valid, {} {} checked",
+            style("ok:").green(),
+            if args.paths.len() == 1 { "is" } else { "are" },
+            requests.len(),
+            if requests.len() == 1 {
+                "request"
+            } else {
+                "requests"
+            }
+        );
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!(
+            "{} {count} {} found in {paths}",
+            style("error:").red(),
+            if count == 1 { "problem" } else { "problems" },
+        );
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// A config read for checking, along with the problems found while reading it.
+struct Source {
+    path: PathBuf,
+    toml: String,
+    problems: Vec<Problem>,
+
+    /// Lines whose templates could not be expanded, already reported in `problems`.
+    unresolved: HashSet<usize>,
+    parsed: Option<Parsed>,
+}
+
+struct Parsed {
+    config: Config,
+
+    /// The config with the settings inherited from the configs including it.
+    inherited: Config,
+    located: Located,
+}
+
+/// Reads a config and the configs it includes, in the order their requests run.
+fn collect(
+    path: &Path,
+    parent: &Config,
+    envs: &HashMap<String, String>,
+    including: &mut Vec<PathBuf>,
+    seen: &mut HashSet<PathBuf>,
+    sources: &mut Vec<Source>,
+) -> Result<()> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if !seen.insert(canonical.clone()) {
+        return Ok(());
+    }
+
+    let toml =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let (toml, unresolved) = template::render_all(path, &toml, &load::variables(parent, envs))?;
+    let mut problems = vec![];
+    let mut unresolved_lines = HashSet::new();
+    for (line, error) in unresolved {
+        unresolved_lines.insert(line);
+        problems.push(Problem {
+            line: Some(line),
+            message: format!("{error:#}"),
+        });
+    }
+
+    let config = match toml::from_str::<Config>(&toml) {
+        Ok(config) => config,
+        Err(error) => {
+            problems.push(Problem {
+                line: error.span().map(|span| line_of(&toml, span.start)),
+                message: error.message().trim_end().to_string(),
+            });
+            sources.push(Source {
+                path: path.to_path_buf(),
+                toml,
+                problems,
+                unresolved: unresolved_lines,
+                parsed: None,
+            });
+            return Ok(());
+        }
+    };
+
+    let located = toml::from_str::<Located>(&toml)
+        .with_context(|| format!("Toml in path {} is not formatted correctly", path.display()))?;
+    let mut inherited = config.clone();
+    load::inherit(&mut inherited, parent);
+
+    including.push(canonical);
+    let include_line = located
+        .include
+        .as_ref()
+        .map(|include| line_of(&toml, include.span().start));
+    let config_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut includes = vec![];
+    for pattern in &config.include {
+        match load::include_paths(config_dir, pattern) {
+            Ok(paths) => {
+                for include in paths {
+                    if including.contains(&include.canonicalize()?) {
+                        problems.push(Problem {
+                            line: include_line,
+                            message: format!("{} includes itself", include.display()),
+                        });
+                    } else {
+                        includes.push(include);
+                    }
+                }
+            }
+            Err(error) => problems.push(Problem {
+                line: include_line,
+                message: format!("{error:#}"),
+            }),
+        }
+    }
+
+    sources.push(Source {
+        path: path.to_path_buf(),
+        toml,
+        problems,
+        unresolved: unresolved_lines,
+        parsed: Some(Parsed {
+            config,
+            inherited: inherited.clone(),
+            located,
+        }),
+    });
+
+    for include in includes {
+        collect(&include, &inherited, envs, including, seen, sources)?;
+    }
+    including.pop();
+
+    Ok(())
+}
+
+/// 1-based line of a byte offset in a config.
+fn line_of(toml: &str, offset: usize) -> usize {
+    toml[..offset.min(toml.len())].matches('\n').count() + 1
+}
+
+struct Problem {
+    line: Option<usize>,
+    message: String,
+}
+
+/// Positions of the values worth pointing at in a config, parsed next to the config itself.
+#[derive(Default, Deserialize)]
+struct Located {
+    include: Option<Spanned<Vec<String>>>,
+    ignore_paths: Option<Spanned<Vec<String>>>,
+    timeout: Option<Spanned<f64>>,
+    retries: Option<Spanned<u32>>,
+    rate_limit: Option<Spanned<f64>>,
+
+    #[serde(default)]
+    left: LocatedPart,
+
+    #[serde(default)]
+    right: LocatedPart,
+
+    #[serde(default)]
+    requests: Vec<LocatedRequest>,
+}
+
+#[derive(Deserialize)]
+struct LocatedRequest {
+    name: Spanned<String>,
+    path: Option<Spanned<String>>,
+    ignore_paths: Option<Spanned<Vec<String>>>,
+
+    #[serde(default)]
+    left: LocatedPart,
+
+    #[serde(default)]
+    right: LocatedPart,
+}
+
+#[derive(Default, Deserialize)]
+struct LocatedPart {
+    base_url: Option<Spanned<String>>,
+    url: Option<Spanned<String>>,
+    method: Option<Spanned<String>>,
+    auth: Option<Spanned<toml::Table>>,
+    body_file: Option<Spanned<String>>,
+    ignore_paths: Option<Spanned<Vec<String>>>,
+    expect_status: Option<Spanned<u16>>,
+    timeout: Option<Spanned<f64>>,
+    retries: Option<Spanned<u32>>,
+
+    #[serde(default)]
+    headers: BTreeMap<Spanned<String>, toml::Value>,
+
+    #[serde(default)]
+    extract: BTreeMap<Spanned<String>, toml::Value>,
+}
+
+struct Checker<'a> {
+    toml: &'a str,
+    path: &'a Path,
+    config_dir: &'a Path,
+    problems: Vec<Problem>,
+    unresolved: &'a HashSet<usize>,
+}
+
+impl Checker<'_> {
+    fn check(
+        &mut self,
+        parsed: &Parsed,
+        names: &mut HashMap<String, String>,
+        [left_captures, right_captures]: &[HashMap<String, String>; 2],
+    ) {
+        let Parsed {
+            config,
+            inherited,
+            located,
+        } = parsed;
+
+        let line = self.located_line(located.ignore_paths.as_ref());
+        self.check_ignore_paths("Config", &config.ignore_paths, line);
+
+        let ranges = [
+            (
+                self.located_line(located.timeout.as_ref()),
+                timeout_duration(config.timeout).map(drop),
+            ),
+            (
+                self.located_line(located.retries.as_ref()),
+                check_retries(config.retries),
+            ),
+            (
+                self.located_line(located.rate_limit.as_ref()),
+                config.rate_interval().map(drop),
+            ),
+        ];
+        for (line, result) in ranges {
+            if let Err(error) = result {
+                self.report(line, format!("Config: {error:#}"));
+            }
+        }
+
+        for (side, defaults, located_defaults) in [
+            ("left", &config.left, &located.left),
+            ("right", &config.right, &located.right),
+        ] {
+            self.check_defaults(&format!("Default {side} side"), defaults, located_defaults);
+        }
+
+        let empty_part = LocatedPart::default();
+        for (index, request) in config.requests.iter().enumerate() {
+            let located_request = located.requests.get(index);
+            let line = located_request.map(|located| self.spanned_line(&located.name));
+
+            let location = match line {
+                Some(line) => format!("{}:{line}", self.path.display()),
+                None => self.path.display().to_string(),
+            };
+            match names.entry(request.name.clone()) {
+                Entry::Occupied(first) => {
+                    self.report(
+                        line,
+                        format!(
+                            "Request name {} is already used at {}",
+                            request.name,
+                            first.get()
+                        ),
+                    );
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(location);
+                }
+            }
+
+            let context = format!("Request {}", request.name);
+            let ignore_line = located_request
+                .and_then(|located| self.located_line(located.ignore_paths.as_ref()))
+                .or(line);
+            self.check_ignore_paths(&context, &request.ignore_paths, ignore_line);
+
+            for (side, part, defaults, captures, located_part) in [
+                (
+                    "left",
+                    &request.left,
+                    (&inherited.left, &located.left),
+                    left_captures,
+                    located_request.map(|located| &located.left),
+                ),
+                (
+                    "right",
+                    &request.right,
+                    (&inherited.right, &located.right),
+                    right_captures,
+                    located_request.map(|located| &located.right),
+                ),
+            ] {
+                let located_part = located_part.unwrap_or(&empty_part);
+                let context = format!("Request {}, {side} side", request.name);
+                self.check_part(&context, part, located_part, line);
+
+                let url_line = self
+                    .located_line(located_part.url.as_ref())
+                    .or_else(|| {
+                        located_request.and_then(|located| self.located_line(located.path.as_ref()))
+                    })
+                    .or(line);
+                let mut resolved = part.clone();
+                if let Err(error) = resolved.inherit(defaults.0, request.path.as_deref()) {
+                    self.report(url_line, format!("{context}: {error:#}"));
+                    continue;
+                }
+
+                match captures::apply(&resolved, captures) {
+                    Ok(resolved) => {
+                        self.check_joined_url(&context, &resolved.url, defaults, url_line);
+                    }
+                    Err(error) => {
+                        self.report(line, format!("{context}: {}", error.root_cause()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks a request's URL, unless it is joined to a base URL of this config that failed to
+    /// expand and so is already reported.
+    fn check_joined_url(
+        &mut self,
+        context: &str,
+        url: &str,
+        (defaults, located): (&PartDefaults, &LocatedPart),
+        line: Option<usize>,
+    ) {
+        let unresolved_base = self
+            .located_line(located.base_url.as_ref())
+            .is_some_and(|line| self.unresolved.contains(&line));
+        if unresolved_base
+            && defaults
+                .base_url
+                .as_ref()
+                .is_some_and(|base_url| url.starts_with(base_url.trim_end_matches('/')))
+        {
+            return;
+        }
+
+        self.check_url(context, url, line);
+    }
+
+    fn check_defaults(&mut self, context: &str, defaults: &PartDefaults, located: &LocatedPart) {
+        if let Some(base_url) = &defaults.base_url {
+            let line = self.located_line(located.base_url.as_ref());
+            self.check_url(context, base_url, line);
+        }
+
+        self.check_headers(context, &defaults.headers, located, None);
+        if let Some(auth) = &defaults.auth {
+            let line = self.located_line(located.auth.as_ref());
+            self.check_auth(context, auth, line);
+        }
+    }
+
+    /// Checks what a side sets itself, its URL is checked once resolved.
+    fn check_part(
+        &mut self,
+        context: &str,
+        part: &PartRequestConfig,
+        located: &LocatedPart,
+        line: Option<usize>,
+    ) {
+        if let Some(method) = &part.method
+            && !METHODS.contains(&method.as_str())
+        {
+            let method_line = self.located_line(located.method.as_ref()).or(line);
+            self.report(
+                method_line,
+                format!(
+                    "{context}: unknown method {method}, expected one of {}",
+                    METHODS.join(", ")
+                ),
+            );
+        }
+
+        self.check_headers(context, &part.headers, located, line);
+
+        if let Some(auth) = &part.auth {
+            let auth_line = self.located_line(located.auth.as_ref()).or(line);
+            self.check_auth(context, auth, auth_line);
+        }
+
+        let mut body_part = part.clone();
+        if let Some(body_file) = &part.body_file {
+            body_part.body_file = Some(self.config_dir.join(body_file));
+        }
+        if let Err(error) = body_part.body() {
+            let body_line = self.located_line(located.body_file.as_ref()).or(line);
+            self.report(body_line, format!("{context}: {error:#}"));
+        }
+
+        let ignore_line = self.located_line(located.ignore_paths.as_ref()).or(line);
+        self.check_ignore_paths(context, &part.ignore_paths, ignore_line);
+
+        let ranges = [
+            (
+                self.located_line(located.timeout.as_ref()),
+                part.timeout_duration().map(drop),
+            ),
+            (
+                self.located_line(located.retries.as_ref()),
+                check_retries(part.retries),
+            ),
+            (
+                self.located_line(located.expect_status.as_ref()),
+                check_status(part.expect_status),
+            ),
+        ];
+        for (range_line, result) in ranges {
+            if let Err(error) = result {
+                self.report(range_line.or(line), format!("{context}: {error:#}"));
+            }
+        }
+
+        for (name, path) in &part.extract {
+            if !path.is_empty()
+                && !path.starts_with('/')
+                && let Err(error) = JsonPath::parse(path)
+            {
+                let extract_line = located
+                    .extract
+                    .keys()
+                    .find(|key| key.get_ref() == name)
+                    .map(|key| self.spanned_line(key))
+                    .or(line);
+                self.report(
+                    extract_line,
+                    format!("{context}: invalid JSON path for capture {name}: {error}"),
+                );
+            }
+        }
+    }
+
+    /// Checks a URL, unless its line is already reported as unresolved, as the URL then still
+    /// holds the template.
+    fn check_url(&mut self, context: &str, url: &str, line: Option<usize>) {
+        if line.is_some_and(|line| self.unresolved.contains(&line)) {
+            return;
+        }
+
+        match Url::parse(url) {
+            Ok(parsed) if !["http", "https"].contains(&parsed.scheme()) => {
+                self.report(line, format!("{context}: URL {url} must use http or https"));
+            }
+            Ok(_) => {}
+            Err(error) => self.report(line, format!("{context}: invalid URL {url}: {error}")),
+        }
+    }
+
+    fn check_headers(
+        &mut self,
+        context: &str,
+        headers: &HashMap<String, String>,
+        located: &LocatedPart,
+        line: Option<usize>,
+    ) {
+        for (name, value) in headers.iter().collect::<BTreeMap<_, _>>() {
+            let header_line = located
+                .headers
+                .keys()
+                .find(|key| key.get_ref() == name)
+                .map(|key| self.spanned_line(key))
+                .or(line);
+
+            if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                self.report(
+                    header_line,
+                    format!("{context}: invalid header name {name:?}"),
+                );
+            }
+
+            if HeaderValue::from_str(value).is_err() {
+                self.report(
+                    header_line,
+                    format!("{context}: invalid value of header {name}"),
+                );
+            }
+        }
+    }
+
+    fn check_auth(&mut self, context: &str, auth: &Auth, line: Option<usize>) {
+        match auth {
+            Auth::Basic(_) => {}
+            Auth::Bearer(bearer) => {
+                let bearer = BearerToken {
+                    token_file: bearer
+                        .token_file
+                        .as_ref()
+                        .map(|token_file| self.config_dir.join(token_file)),
+                    ..bearer.clone()
+                };
+                if let Err(error) = bearer.token() {
+                    self.report(line, format!("{context}: {error:#}"));
+                }
+            }
+            Auth::Oauth2ClientCredentials(credentials) => {
+                self.check_url(context, &credentials.token_url, line);
+            }
+        }
+    }
+
+    fn check_ignore_paths(&mut self, context: &str, paths: &[String], line: Option<usize>) {
+        for path in paths {
+            if !path.is_empty()
+                && !path.starts_with('/')
+                && let Err(error) = JsonPath::parse(path)
+            {
+                self.report(
+                    line,
+                    format!("{context}: invalid JSONPath in ignore paths {path}: {error}"),
+                );
+            }
+        }
+    }
+
+    fn report(&mut self, line: Option<usize>, message: String) {
+        self.problems.push(Problem { line, message });
+    }
+
+    fn located_line<T>(&self, spanned: Option<&Spanned<T>>) -> Option<usize> {
+        spanned.map(|spanned| self.spanned_line(spanned))
+    }
+
+    fn spanned_line<T>(&self, spanned: &Spanned<T>) -> usize {
+        line_of(self.toml, spanned.span().start)
+    }
+}